@@ -1,72 +1,49 @@
 #![feature(hasher_prefixfree_extras, is_sorted, iter_advance_by, let_else)]
 #![feature(slice_partition_dedup)]
 
+mod manifest;
+mod query_spec;
 mod rdf;
 mod sparql;
 mod util;
+mod workload;
 
-use clap::{ArgEnum, Parser, Subcommand};
+use clap::{ArgEnum, ArgGroup, Parser, Subcommand};
+use manifest::Manifest;
 use memory_mapped::MemoryMapped;
-use rdf::triple_compressor::{
-    compressor::RdfTripleCompressor, decompressor::RdfTripleDecompressor, CompressedRdfTriples,
-    COMPRESSED_TRIPLE_FILE_EXTENSION, UNCOMPRESSED_TRIPLE_FILE_EXTENSION,
+use query_spec::{QuerySpecOpt, SizeReferences};
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+use rdf::{
+    format::RdfFormat,
+    prefix::{PrefixDecl, PrefixMap},
+    term::NTriplesTerm,
+    triple_compressor::{
+        compressor::{RdfTripleCompressor, TermPosition},
+        decompressor::RdfTripleDecompressor,
+        dictionary::{export_dictionary, import_dictionary, DictionaryFormat},
+        CompressedRdfTriples, TripleElementId, TripleSet, COMPRESSED_TRIPLE_FILE_EXTENSION,
+        UNCOMPRESSED_TRIPLE_FILE_EXTENSION,
+    },
+    triple_generator::{Changeset, ChangesetExhaustion, ChangesetReuse, ChangesetSelection},
 };
-use sparql::OutputOrder;
-use std::{collections::HashSet, hash::BuildHasherDefault, path::PathBuf, str::FromStr};
-use util::{changeset_file_iter, dataset_iter};
-
-#[derive(Clone, Copy)]
-pub struct QuerySpecOpt {
-    n_queries: usize,
-    n_triples_per_query: QuerySizeOpt,
-}
-
-#[derive(Clone, Copy)]
-pub enum QuerySizeOpt {
-    Percentage(f64),
-    Absolute(usize),
-}
-
-impl FromStr for QuerySpecOpt {
-    type Err = String;
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let (n_queries, n_triples_per_query) = s
-            .split_once('x')
-            .ok_or_else(|| "invalid query spec, expected delimiter".to_owned())?;
-
-        let n_queries = n_queries
-            .parse()
-            .map_err(|e| format!("invalid query spec, first value is not integer: {e:?}"))?;
-
-        let n_triples_per_query = if n_triples_per_query.ends_with('%') {
-            QuerySizeOpt::Percentage(
-                n_triples_per_query
-                    .trim_end_matches('%')
-                    .parse::<f64>()
-                    .map_err(|e| format!("invalid query spec, triple count specifier is not integer: {e:?}"))?
-                    / 100.0,
-            )
-        } else {
-            QuerySizeOpt::Absolute(
-                n_triples_per_query
-                    .parse()
-                    .map_err(|e| format!("invalid query spec, triple count specifier is not integer: {e:?}"))?,
-            )
-        };
-
-        Ok(QuerySpecOpt { n_queries, n_triples_per_query })
-    }
-}
-
-impl QuerySizeOpt {
-    pub fn get_absolute(self, n_total_triples: usize) -> usize {
-        match self {
-            QuerySizeOpt::Absolute(n) => n,
-            QuerySizeOpt::Percentage(percent) => (n_total_triples as f64 * percent) as usize,
-        }
-    }
-}
+use serde::Deserialize;
+use sparql::{
+    miss::{MissSource, Misses},
+    output::{OutputOpts, QueryOutput},
+    overlap::{Overlap, OverlapTarget},
+    replay::ReplayMode,
+    state::{StateTracking, StoreSimulation},
+    OutputOrder, PatternKind, PatternOperation, QueryAdjustments, UpdateKind,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    fs::File,
+    hash::BuildHasherDefault,
+    io::{BufReader, BufWriter},
+    path::{Path, PathBuf},
+};
+use util::{changeset_file_iter, changeset_pairs, dataset_iter, with_file_name_suffix};
+use workload::{ReplicateSource, Workload};
 
 #[derive(Parser)]
 #[clap(author, version, about)]
@@ -89,10 +66,14 @@ enum Opts {
         #[clap(short = 'D', long, action)]
         dedup: bool,
 
+        /// Canonicalize IRIs, language tags and numeric, boolean and dateTime literals before compressing them
+        #[clap(short = 'c', long, action)]
+        canonicalize: bool,
+
         /// Datasets to compress
         datasets: Vec<PathBuf>,
     },
-    /// Generate SPARQL DELETE DATA queries from a compressed dataset
+    /// Generate SPARQL DELETE DATA (or INSERT DATA) queries from a compressed dataset
     Generate {
         /// Path to the associated compressor state
         #[clap(short = 's', long)]
@@ -102,47 +83,132 @@ enum Opts {
         #[clap(short = 'i', long)]
         compressed_dataset: PathBuf,
 
-        /// File to write the query to
-        #[clap(short = 'o', long)]
-        query_out: PathBuf,
+        #[clap(flatten)]
+        output: OutputOpts,
 
         /// Set the order of the generated queries
         #[clap(arg_enum, short = 'r', long, default_value_t = OutputOrder::AsSpecified)]
         output_order: OutputOrder,
 
-        /// Append to query-out instead of overwriting it
-        #[clap(short, long, action)]
-        append: bool,
+        /// Set the kind of the generated update queries
+        #[clap(arg_enum, short = 'u', long, default_value_t = UpdateKind::Delete)]
+        update_kind: UpdateKind,
+
+        /// Simulate the store state through the queries in output order, starting with the main dataset,
+        /// to report or drop triples that would not change the store when their query runs
+        #[clap(arg_enum, long, default_value_t = StateTracking::Off)]
+        state_tracking: StateTracking,
+
+        /// Fraction (0 to 1) of the triples of every query (not counting misses) that are taken from earlier queries
+        /// in output order. Exact if the generator itself produces distinct queries, e.g. randomized without
+        /// --allow-duplicates
+        #[clap(long)]
+        overlap: Option<f64>,
+
+        /// Which earlier queries the overlapping triples are taken from
+        #[clap(arg_enum, long, default_value_t = OverlapTarget::Preceding)]
+        overlap_with: OverlapTarget,
+
+        /// Fraction (0 to 1) of the triples of every query that are replaced by triples not contained
+        /// in the main dataset. Only for DELETE DATA queries
+        #[clap(long)]
+        misses: Option<f64>,
+
+        /// Take the miss triples from this compressed dataset instead of recombining subjects,
+        /// predicates and objects of the main dataset
+        #[clap(long, requires("misses"))]
+        miss_dataset: Option<PathBuf>,
+
+        /// Compressed datasets (or directories of them) whose triples are never used, e.g. a held-out set
+        /// or re-compressed earlier query outputs. Relative sizes refer to the remaining triples
+        #[clap(short = 'x', long = "exclude")]
+        excluded_datasets: Vec<PathBuf>,
+
+        /// Stop adding triples to a query once the whole query would take up more than this many bytes,
+        /// e.g. to respect request body limits of the endpoint. Only for the plain serialization,
+        /// i.e. without prefixes and pretty printing. Not for pattern generation, misses and overlap
+        #[clap(long, value_parser = clap::value_parser!(u64).range(1..))]
+        max_bytes: Option<u64>,
+
+        /// Seed for all random choices. If not given a random seed is chosen.
+        /// The seed is recorded in the manifest written next to query-out (<QUERY_OUT>.manifest.jsonl)
+        #[clap(long, global(true))]
+        seed: Option<u64>,
 
         #[clap(subcommand)]
         g_type: GenerateType,
 
-        /// Query specs of the form <N_QUERIES>x<N_TRIPLE_PER_QUERY>
+        /// File with additional query specs, one per line. Empty lines and lines starting with # are ignored
+        #[clap(long, global(true))]
+        query_spec_file: Option<PathBuf>,
+
+        /// Query specs of the form <N_QUERIES>x<SIZE>. SIZE is a single size, a range <MIN>-<MAX>
+        /// or one of the distributions uniform(<MIN>,<MAX>), normal(<MEAN>,<STD_DEV>),
+        /// lognormal(<MEDIAN>,<SIGMA>) and zipf(<MAX>,<EXPONENT>). Sizes are absolute,
+        /// a percentage of the main dataset (e.g. 1.5%) or of all changesets together (e.g. 1.5%c)
         #[clap(value_parser, global(true))]
         query_specs: Vec<QuerySpecOpt>,
     },
-    /// Generate SPARQL DELETE DATA queries by replicating the given compressed datasets
+    /// Generate SPARQL DELETE DATA (or INSERT DATA) queries by replicating the given compressed datasets
+    #[clap(group(ArgGroup::new("limits").multiple(true).args(&["max-triples", "max-bytes"])))]
     Replicate {
         /// Path to the associated compressor state
         #[clap(short = 's', long)]
         compressor_state: PathBuf,
 
-        /// File to write the query to
-        #[clap(short = 'o', long)]
-        query_out: PathBuf,
+        #[clap(flatten)]
+        output: OutputOpts,
 
         /// Operate recursively on directories
         #[clap(short = 'r', long, action)]
         recursive: bool,
 
-        /// Append to query-out instead of overwriting it
-        #[clap(short, long, action)]
-        append: bool,
+        /// Set the kind of the generated update queries
+        #[clap(arg_enum, short = 'u', long, default_value_t = UpdateKind::Delete)]
+        update_kind: UpdateKind,
+
+        /// Split datasets into queries of at most this many triples
+        #[clap(long, value_parser = clap::value_parser!(u64).range(1..))]
+        max_triples: Option<u64>,
+
+        /// Split datasets into queries of at most this many bytes, for delete-insert each of the paired queries.
+        /// Only for the plain serialization, i.e. without prefixes and pretty printing
+        #[clap(long, value_parser = clap::value_parser!(u64).range(1..))]
+        max_bytes: Option<u64>,
+
+        /// Merge consecutive datasets into one query as long as they fit into max-triples and max-bytes as a whole,
+        /// larger datasets are still split. Requires at least one of the limits
+        #[clap(short, long, action, requires = "limits")]
+        merge: bool,
 
         /// The datasets to replicate
         compressed_datasets: Vec<PathBuf>,
     },
-    /// Decompress compressed datasets back into n-triple files
+    /// Generate queries replaying the changesets of a live system (e.g. DBpedia Live) in the order they were applied.
+    /// Changesets are pairs of <NAME>.removed.compressed_nt and <NAME>.added.compressed_nt files,
+    /// ordered by the timestamp embedded in their names and directories
+    Replay {
+        /// Path to the associated compressor state
+        #[clap(short = 's', long)]
+        compressor_state: PathBuf,
+
+        #[clap(flatten)]
+        output: OutputOpts,
+
+        /// Whether every changeset becomes separate DELETE DATA and INSERT DATA queries or a single combined query
+        #[clap(arg_enum, short = 'm', long, default_value_t = ReplayMode::Separate)]
+        mode: ReplayMode,
+
+        /// Directory tree containing the compressed changesets
+        compressed_changesets: PathBuf,
+    },
+    /// Run the phases of a workload file (TOML or YAML) one after another. Each phase is a generate
+    /// or replicate run, all of them share a single loaded compressor state and main dataset
+    RunWorkload {
+        /// The workload file, its format is chosen by the extension (.toml, .yaml or .yml)
+        workload: PathBuf,
+    },
+    /// Decompress compressed datasets back into n-triple (or other RDF format) files
     Decompress {
         /// Path to the associated compressor state
         #[clap(short = 's', long)]
@@ -152,6 +218,22 @@ enum Opts {
         #[clap(short = 'r', long, action)]
         recursive: bool,
 
+        /// Output format of the decompressed datasets
+        #[clap(arg_enum, short = 'f', long, default_value_t = RdfFormat::NTriples)]
+        format: RdfFormat,
+
+        /// Prefix declarations of the form <PREFIX>=<NAMESPACE_IRI> used to abbreviate IRIs in turtle and json-ld output
+        #[clap(short = 'p', long = "prefix", value_parser)]
+        prefixes: Vec<PrefixDecl>,
+
+        /// Additionally declare prefixes for the N most frequent IRI namespaces in the compressor state
+        #[clap(short = 'l', long, default_value_t = 0)]
+        learn_prefixes: usize,
+
+        /// IRI of the named graph to put the triples into for nquads and jsonld output
+        #[clap(short = 'g', long)]
+        graph: Option<String>,
+
         /// The datasets to replicate
         compressed_datasets: Vec<PathBuf>,
     },
@@ -185,18 +267,118 @@ enum Opts {
         /// The datasets to check against the main dataset
         compressed_datasets: Vec<PathBuf>,
     },
+    /// Resolve terms to ids and ids to terms, and check whether triples are contained in compressed datasets
+    Lookup {
+        /// Path to the associated compressor state
+        #[clap(short = 's', long)]
+        compressor_state: PathBuf,
+
+        /// Canonicalize terms and triples before looking them up,
+        /// required if the compressor state was created with --canonicalize
+        #[clap(short = 'c', long, action)]
+        canonicalize: bool,
+
+        /// Terms in n-triples syntax to resolve to their ids, e.g. '<http://example.org/a>'
+        #[clap(short = 't', long = "term")]
+        terms: Vec<String>,
+
+        /// Ids to resolve to their terms
+        #[clap(short = 'i', long = "id")]
+        ids: Vec<TripleElementId>,
+
+        /// Triples in n-triples syntax to search for in compressed-datasets
+        #[clap(short = 'T', long = "triple")]
+        triples: Vec<String>,
+
+        /// Operate recursively on directories
+        #[clap(short = 'r', long, action)]
+        recursive: bool,
+
+        /// The datasets to search the triples in
+        compressed_datasets: Vec<PathBuf>,
+    },
+    /// Export the term dictionary (id to term table) of a compressor state as TSV or CSV
+    ExportDictionary {
+        /// Path to the compressor state to export
+        #[clap(short = 's', long)]
+        compressor_state: PathBuf,
+
+        /// File to write the dictionary to
+        #[clap(short = 'o', long)]
+        dictionary_out: PathBuf,
+
+        /// Format of the dictionary
+        #[clap(arg_enum, short = 'f', long, default_value_t = DictionaryFormat::Tsv)]
+        format: DictionaryFormat,
+    },
+    /// Build a compressor state from a TSV or CSV term dictionary
+    ImportDictionary {
+        /// The dictionary to import, terms must be in n-triples syntax
+        #[clap(short = 'i', long)]
+        dictionary: PathBuf,
+
+        /// Path to file in which the resulting compressor state should be written
+        #[clap(short = 'o', long)]
+        compressor_state_out: PathBuf,
+
+        /// Format of the dictionary
+        #[clap(arg_enum, short = 'f', long, default_value_t = DictionaryFormat::Tsv)]
+        format: DictionaryFormat,
+
+        /// Require every id to be the id this tool would assign to its term.
+        /// Without this ids from other tools are accepted as is
+        #[clap(short = 'V', long, action)]
+        verify_ids: bool,
+    },
 }
 
-#[derive(Subcommand)]
+#[derive(Subcommand, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case", deny_unknown_fields)]
 enum GenerateType {
     /// derives the queries by selecting random triples from the dataset
     Randomized {
         /// allow the generator to generate distinct queries
         /// with common triples
         #[clap(short = 'd', long, action)]
+        #[serde(default)]
         allow_duplicates: bool,
     },
 
+    /// derives the queries by selecting whole entities (all triples of a subject) from the dataset
+    Entity {
+        /// allow splitting an entity at the boundary between two queries to fill queries
+        /// to exactly the requested size
+        #[clap(short = 'p', long, action)]
+        #[serde(default)]
+        allow_partial: bool,
+    },
+
+    /// derives the queries by selecting random triples stratified by predicate,
+    /// either with an equal share per predicate or weighted by a weight table
+    Stratified {
+        /// File with lines of the form `<PREDICATE_IRI> <WEIGHT>`, predicates missing from the file are not selected.
+        /// Without this file every predicate gets an equal share of each query
+        #[clap(short = 'w', long)]
+        predicate_weights: Option<PathBuf>,
+
+        /// Canonicalize the weighted predicates before looking them up,
+        /// required if the compressor state was created with --canonicalize
+        #[clap(long, action, requires = "predicate-weights")]
+        #[serde(default)]
+        canonicalize: bool,
+    },
+
+    /// derives the queries by selecting random subjects weighted by their degree (number of triples)
+    /// and a random triple of each selected subject
+    DegreeBiased {
+        /// subjects are selected with probability proportional to degree^EXPONENT.
+        /// 1 is equivalent to uniform triple sampling, larger values favour high degree subjects,
+        /// smaller values favour low degree subjects
+        #[clap(short = 'e', long, default_value_t = default_degree_exponent(), allow_hyphen_values = true)]
+        #[serde(default = "default_degree_exponent")]
+        exponent: f64,
+    },
+
     /// derives the queries from a set of changesets
     Changeset {
         /// Path to the compressed changeset file or directory tree containing the compressed changesets.
@@ -205,21 +387,579 @@ enum GenerateType {
 
         /// Query generation type
         #[clap(arg_enum, short = 't', long = "type", default_value_t = GenerateChangesetType::AsIs)]
+        #[serde(default, rename = "changeset_type")]
         generate_type: GenerateChangesetType,
+
+        /// What fixed-size generation does once all changesets are used
+        #[clap(arg_enum, long, default_value_t = ChangesetExhaustion::Wrap)]
+        #[serde(default)]
+        when_exhausted: ChangesetExhaustion,
+
+        /// Whether as-is generation may use a changeset for more than one query
+        #[clap(arg_enum, long, default_value_t = ChangesetReuse::Never)]
+        #[serde(default)]
+        reuse: ChangesetReuse,
+
+        /// For penalized reuse, every earlier use of a changeset counts as a size difference of
+        /// REUSE_PENALTY times the requested size
+        #[clap(long, default_value_t = default_reuse_penalty())]
+        #[serde(default = "default_reuse_penalty")]
+        reuse_penalty: f64,
+
+        /// As-is generation never selects changesets whose size differs by more than this fraction
+        /// of the requested size (e.g. 0.1 for 10%)
+        #[clap(long)]
+        size_tolerance: Option<f64>,
+    },
+
+    /// derives pattern based queries (e.g. DELETE WHERE { <s> ?p ?o }) from distinct random subjects
+    /// or subject-predicate pairs of the dataset.
+    /// Query specs are interpreted as <N_QUERIES>x<N_PATTERNS_PER_QUERY>, percentages are relative to
+    /// the number of distinct subjects or subject-predicate pairs.
+    /// Cannot be combined with state tracking, overlap, misses, update kinds other than delete and max bytes
+    Pattern {
+        /// What the patterns select
+        #[clap(arg_enum, short = 'k', long, default_value_t = PatternKind::Subject)]
+        #[serde(default)]
+        kind: PatternKind,
+
+        /// The update operation of each pattern
+        #[clap(arg_enum, short = 'p', long, default_value_t = PatternOperation::DeleteWhere)]
+        #[serde(default)]
+        operation: PatternOperation,
     },
 }
 
-#[derive(ArgEnum, Clone)]
+fn default_degree_exponent() -> f64 {
+    2.0
+}
+
+fn default_reuse_penalty() -> f64 {
+    0.5
+}
+
+#[derive(ArgEnum, Clone, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
 enum GenerateChangesetType {
     /// tries to fulfill the requested query sizes as closely as possible
     /// with the existing changesets
+    #[default]
     AsIs,
 
-    /// truncates or stitches changesets together to fulfill the requested
-    /// sizes exactly
+    /// walks through the changesets in order and truncates or stitches them together
+    /// to fulfill the requested sizes exactly
     FixedSize,
 }
 
+/// Loads a predicate weight table with lines of the form `<PREDICATE_IRI> <WEIGHT>` and resolves the predicates to their
+/// ids, canonicalizing them first if `canonicalize` is set. Empty lines and lines starting with `#` are ignored.
+fn load_predicate_weights(
+    path: &Path,
+    canonicalize: bool,
+) -> Result<HashMap<TripleElementId, f64>, Box<dyn std::error::Error>> {
+    let mut weights = HashMap::new();
+
+    for (line_no, line) in std::fs::read_to_string(path)?.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (predicate, weight) = line
+            .rsplit_once(char::is_whitespace)
+            .ok_or_else(|| format!("{path:?} line {}: expected <PREDICATE_IRI> <WEIGHT>", line_no + 1))?;
+
+        let weight: f64 = weight
+            .parse()
+            .map_err(|e| format!("{path:?} line {}: invalid weight: {e:?}", line_no + 1))?;
+
+        if !weight.is_finite() || weight < 0.0 {
+            return Err(format!("{path:?} line {}: weight must be finite and not negative", line_no + 1).into());
+        }
+
+        let predicate = predicate.trim();
+        let predicate_id = if predicate.starts_with('<') {
+            RdfTripleCompressor::rdf_term_id(predicate, TermPosition::Predicate, canonicalize)?
+        } else {
+            RdfTripleCompressor::rdf_term_id(&format!("<{predicate}>"), TermPosition::Predicate, canonicalize)?
+        };
+
+        weights.insert(predicate_id, weight);
+    }
+
+    Ok(weights)
+}
+
+/// Everything a single generation run needs besides the loaded compressor state and main dataset
+struct GenerateJob {
+    output: QueryOutput,
+    query_specs: Vec<QuerySpecOpt>,
+    query_spec_file: Option<PathBuf>,
+    g_type: GenerateType,
+    output_order: OutputOrder,
+    update_kind: UpdateKind,
+    state_tracking: StateTracking,
+    overlap: Option<f64>,
+    overlap_with: OverlapTarget,
+    misses: Option<f64>,
+    miss_dataset: Option<PathBuf>,
+    excluded_datasets: Vec<PathBuf>,
+    max_bytes: Option<usize>,
+    seed: Option<u64>,
+
+    /// the workload file if the job is a phase of a workload, recorded in the manifest
+    workload_file: Option<PathBuf>,
+}
+
+fn load_main_dataset<P: AsRef<Path>>(compressed_dataset: P) -> std::io::Result<CompressedRdfTriples> {
+    println!("loading main dataset...");
+    let dataset_triples = unsafe { CompressedRdfTriples::load(compressed_dataset)? };
+    assert!(
+        dataset_triples.is_sorted(),
+        "dataset triples must be compressed with -D to ensure correct query generation"
+    );
+
+    println!("loaded {} distinct triples from main dataset", dataset_triples.len());
+    Ok(dataset_triples)
+}
+
+/// Runs `job` against an already loaded compressor state and main dataset,
+/// `compressor_state` and `compressed_dataset` are only used to fingerprint them in the manifest
+fn generate(
+    compressor_state: &Path,
+    compressed_dataset: &Path,
+    decompressor: &RdfTripleDecompressor,
+    dataset_triples: &CompressedRdfTriples,
+    GenerateJob {
+        output,
+        query_specs,
+        query_spec_file,
+        g_type,
+        output_order,
+        update_kind,
+        state_tracking,
+        overlap,
+        overlap_with,
+        misses,
+        miss_dataset,
+        excluded_datasets,
+        max_bytes,
+        seed,
+        workload_file,
+    }: GenerateJob,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let seed = seed.unwrap_or_else(rand::random);
+    println!("using seed {seed}");
+
+    // the generator and the output order get independent streams so that they do not influence each other
+    let mut seed_rng = SmallRng::seed_from_u64(seed);
+    let generator_rng = SmallRng::from_rng(&mut seed_rng)?;
+    let mut order_rng = SmallRng::from_rng(&mut seed_rng)?;
+    let mut size_rng = SmallRng::from_rng(&mut seed_rng)?;
+    let overlap_rng = SmallRng::from_rng(&mut seed_rng)?;
+    let miss_rng = SmallRng::from_rng(&mut seed_rng)?;
+
+    for (name, ratio) in [("overlap", overlap), ("misses", misses)] {
+        if let Some(ratio) = ratio {
+            if !(0.0..=1.0).contains(&ratio) {
+                return Err(format!("{name} must be between 0 and 1, got {ratio}").into());
+            }
+        }
+    }
+
+    if misses.is_some() && !matches!(update_kind, UpdateKind::Delete) {
+        return Err("misses can only be injected into DELETE DATA queries".into());
+    }
+
+    if max_bytes.is_some() && !output.serialization.is_plain() {
+        return Err("max bytes can only be used without prefixes and pretty printing".into());
+    }
+
+    // misses and overlapping triples replace triples after the size of a query has been measured
+    if max_bytes.is_some() && (misses.is_some() || overlap.is_some()) {
+        return Err("max bytes cannot be combined with misses or overlap".into());
+    }
+
+    if let GenerateType::Pattern { .. } = g_type {
+        let unsupported: Vec<_> = [
+            ("state tracking", !matches!(state_tracking, StateTracking::Off)),
+            ("overlap", overlap.is_some()),
+            ("misses", misses.is_some()),
+            ("update kind", !matches!(update_kind, UpdateKind::Delete)),
+            ("max bytes", max_bytes.is_some()),
+        ]
+        .into_iter()
+        .filter_map(|(name, is_set)| is_set.then_some(name))
+        .collect();
+
+        if !unsupported.is_empty() {
+            return Err(format!("{} cannot be used for pattern queries", unsupported.join(", ")).into());
+        }
+    }
+
+    let mut manifest = Manifest::new(seed);
+    if let Some(path) = workload_file {
+        manifest.add_input("workload", [path]);
+    }
+    manifest.add_input("compressor_state", [compressor_state.to_owned()]);
+    manifest.add_input("main_dataset", [compressed_dataset.to_owned()]);
+
+    let mut excluded = TripleSet::default();
+    let mut excluded_paths = Vec::new();
+
+    for path in dataset_iter(excluded_datasets, true, COMPRESSED_TRIPLE_FILE_EXTENSION) {
+        let path = path?;
+
+        println!("loading excluded triples from {path:?}...");
+        excluded.extend(unsafe { CompressedRdfTriples::load(&path)? }.iter());
+        excluded_paths.push(path);
+    }
+
+    let full_dataset_triples = dataset_triples;
+    let remaining_triples;
+    let dataset_triples = if excluded.is_empty() {
+        dataset_triples
+    } else {
+        manifest.add_input("excluded", excluded_paths);
+
+        // the remaining triples are only mapped while generating, the file is placed next to the output
+        // (instead of a possibly memory backed temp dir) as it can be as large as the main dataset
+        let path = with_file_name_suffix(
+            &output.path,
+            &format!(".{}-remaining.{COMPRESSED_TRIPLE_FILE_EXTENSION}", std::process::id()),
+        );
+
+        println!("removing {} excluded triples from main dataset...", excluded.len());
+        remaining_triples = unsafe { dataset_triples.without(&excluded, &path)? };
+
+        println!("{} triples of the main dataset remain", remaining_triples.len());
+        &remaining_triples
+    };
+
+    // a pattern deletes every matching triple of the store, so the groups are built on the full dataset
+    // and groups containing excluded triples are dropped as a whole
+    let pattern_groups = match g_type {
+        GenerateType::Pattern { kind, .. } => {
+            let groups = match kind {
+                PatternKind::Subject => rdf::triple_generator::subject_ranges(full_dataset_triples),
+                PatternKind::SubjectPredicate => rdf::triple_generator::subject_predicate_ranges(full_dataset_triples),
+            };
+
+            let n_groups = groups.len();
+            let groups: Vec<_> = groups
+                .into_iter()
+                .filter(|group| !full_dataset_triples[group.clone()].iter().any(|triple| excluded.contains(triple)))
+                .collect();
+
+            if groups.len() < n_groups {
+                println!("{} patterns match excluded triples and are not used", n_groups - groups.len());
+            }
+
+            Some(groups)
+        },
+        _ => None,
+    };
+
+    if let GenerateType::Stratified { predicate_weights: Some(path), .. } = &g_type {
+        manifest.add_input("predicate_weights", [path.clone()]);
+    }
+
+    let changesets = match &g_type {
+        GenerateType::Changeset { compressed_changesets, .. } => {
+            let changesets: Vec<_> = changeset_file_iter(compressed_changesets, COMPRESSED_TRIPLE_FILE_EXTENSION)
+                .map(Result::unwrap)
+                .filter_map(|de| match unsafe { CompressedRdfTriples::load(de.path()) } {
+                    Ok(triples) => Some(Changeset { path: de.into_path(), triples }),
+                    Err(e) => {
+                        eprintln!("Error: unable to open {:?}: {e:?}", de.path());
+                        None
+                    },
+                })
+                .collect();
+
+            manifest.add_input("changesets", changesets.iter().map(|changeset| changeset.path.clone()));
+            changesets
+        },
+        _ => Vec::new(),
+    };
+
+    let size_references = SizeReferences {
+        main_dataset: pattern_groups.as_ref().map_or(dataset_triples.len(), Vec::len),
+        changesets: matches!(g_type, GenerateType::Changeset { .. }).then(|| {
+            changesets
+                .iter()
+                .flat_map(|changeset| changeset.triples.iter())
+                .filter(|triple| !excluded.contains(*triple))
+                .count()
+        }),
+    };
+
+    let mut query_specs = query_specs;
+    if let Some(path) = &query_spec_file {
+        query_specs.extend(query_spec::load_query_specs(path)?);
+        manifest.add_input("query_spec_file", [path.clone()]);
+    }
+
+    let query_specs = query_specs
+        .iter()
+        .map(|spec| spec.resolve(&size_references, &mut size_rng))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let miss_triples = match &miss_dataset {
+        Some(path) => {
+            manifest.add_input("miss_dataset", [path.clone()]);
+
+            println!("loading miss dataset...");
+            Some(unsafe { CompressedRdfTriples::load(path)? })
+        },
+        None => None,
+    };
+
+    let adjustments = QueryAdjustments {
+        misses: misses.map(|ratio| Misses {
+            ratio,
+            source: miss_triples.as_ref().map_or(MissSource::Recombined, MissSource::Dataset),
+            dataset: full_dataset_triples,
+            excluded: &excluded,
+            rng: miss_rng,
+        }),
+        overlap: overlap.map(|ratio| Overlap { ratio, target: overlap_with, rng: overlap_rng }),
+        simulation: StoreSimulation::new(dataset_triples, state_tracking),
+        max_bytes,
+    };
+
+    println!("writing manifest...");
+    manifest.write(&output.path, output.append, &query_specs)?;
+
+    match g_type {
+        GenerateType::Changeset {
+            generate_type: GenerateChangesetType::AsIs, reuse, reuse_penalty, size_tolerance, ..
+        } => {
+            println!("generating queries from changesets...");
+
+            // the changesets are selected before generation, in the order of the query specs
+            let query_sizes: Vec<_> = query_specs.iter().flat_map(|spec| spec.query_sizes.clone()).collect();
+            let generator = rdf::triple_generator::as_is_changeset_triple_generator(
+                &changesets,
+                &excluded,
+                query_sizes,
+                ChangesetSelection { reuse, reuse_penalty, size_tolerance },
+            )?;
+
+            sparql::generate_queries(
+                &output,
+                query_specs,
+                decompressor,
+                generator,
+                output_order,
+                update_kind,
+                adjustments,
+                &mut order_rng,
+            )
+        },
+        GenerateType::Changeset { generate_type: GenerateChangesetType::FixedSize, when_exhausted, .. } => {
+            println!("generating fixed size queries from changesets...");
+
+            sparql::generate_queries(
+                &output,
+                query_specs,
+                decompressor,
+                rdf::triple_generator::fixed_size_changeset_triple_generator(
+                    &changesets,
+                    dataset_triples,
+                    when_exhausted,
+                    max_bytes.map(|max_bytes| update_kind.max_body_len(max_bytes)),
+                    |triple| sparql::rendered_triple_len(decompressor, triple),
+                ),
+                output_order,
+                update_kind,
+                adjustments,
+                &mut order_rng,
+            )
+        },
+        GenerateType::Randomized { allow_duplicates: false } => {
+            println!("generating distinct queries from main dataset...");
+
+            let total_query_triples: usize = query_specs.iter().map(sparql::QuerySpec::total_size).sum();
+
+            sparql::generate_queries(
+                &output,
+                query_specs,
+                decompressor,
+                rdf::triple_generator::random_distinct_triple_generator(
+                    dataset_triples,
+                    total_query_triples,
+                    generator_rng,
+                ),
+                output_order,
+                update_kind,
+                adjustments,
+                &mut order_rng,
+            )
+        },
+        GenerateType::Randomized { allow_duplicates: true } => {
+            println!("generating queries from main dataset...");
+
+            sparql::generate_queries(
+                &output,
+                query_specs,
+                decompressor,
+                rdf::triple_generator::random_triple_generator(dataset_triples, generator_rng),
+                output_order,
+                update_kind,
+                adjustments,
+                &mut order_rng,
+            )
+        },
+        GenerateType::Entity { allow_partial } => {
+            println!("generating entity queries from main dataset...");
+
+            sparql::generate_queries(
+                &output,
+                query_specs,
+                decompressor,
+                rdf::triple_generator::entity_triple_generator(dataset_triples, allow_partial, generator_rng),
+                output_order,
+                update_kind,
+                adjustments,
+                &mut order_rng,
+            )
+        },
+        GenerateType::Stratified { predicate_weights, canonicalize } => {
+            let predicate_weights =
+                predicate_weights.map(|path| load_predicate_weights(&path, canonicalize)).transpose()?;
+
+            println!("generating predicate stratified queries from main dataset...");
+
+            sparql::generate_queries(
+                &output,
+                query_specs,
+                decompressor,
+                rdf::triple_generator::predicate_stratified_triple_generator(
+                    dataset_triples,
+                    predicate_weights.as_ref(),
+                    generator_rng,
+                ),
+                output_order,
+                update_kind,
+                adjustments,
+                &mut order_rng,
+            )
+        },
+        GenerateType::DegreeBiased { exponent } => {
+            println!("generating degree biased queries from main dataset...");
+
+            sparql::generate_queries(
+                &output,
+                query_specs,
+                decompressor,
+                rdf::triple_generator::degree_biased_triple_generator(dataset_triples, exponent, generator_rng)?,
+                output_order,
+                update_kind,
+                adjustments,
+                &mut order_rng,
+            )
+        },
+        GenerateType::Pattern { kind, operation } => {
+            println!("generating pattern queries from main dataset...");
+
+            let groups = pattern_groups.expect("pattern groups to be computed for pattern generation");
+
+            let total_query_patterns: usize = query_specs.iter().map(sparql::QuerySpec::total_size).sum();
+
+            if total_query_patterns > groups.len() {
+                println!(
+                    "Warning: requested {total_query_patterns} distinct patterns but the main dataset only has {}",
+                    groups.len()
+                );
+            }
+
+            sparql::generate_pattern_queries(
+                &output,
+                query_specs,
+                decompressor,
+                rdf::triple_generator::random_distinct_group_generator(
+                    full_dataset_triples,
+                    &groups,
+                    total_query_patterns,
+                    generator_rng,
+                ),
+                sparql::PatternSpec { kind, operation },
+                output_order,
+                &mut order_rng,
+            )
+        },
+    }?;
+
+    Ok(())
+}
+
+/// Bounds for the queries generated by [`replicate`]
+struct ReplicateLimits {
+    max_triples: Option<usize>,
+    max_bytes: Option<usize>,
+
+    /// merge consecutive datasets into one query as long as they fit into the limits as a whole
+    merge: bool,
+}
+
+/// Generates one query per dataset in `compressed_datasets`, or several if a dataset exceeds the limits
+fn replicate(
+    decompressor: &RdfTripleDecompressor,
+    compressed_datasets: Vec<PathBuf>,
+    recursive: bool,
+    output: &QueryOutput,
+    update_kind: UpdateKind,
+    ReplicateLimits { max_triples, max_bytes, merge }: ReplicateLimits,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if max_bytes.is_some() && !output.serialization.is_plain() {
+        return Err("max bytes can only be used without prefixes and pretty printing".into());
+    }
+
+    println!("loading datasets to replicate...");
+    let datasets: Vec<_> = dataset_iter(compressed_datasets, recursive, COMPRESSED_TRIPLE_FILE_EXTENSION)
+        .map(Result::unwrap)
+        .filter_map(|p| match unsafe { CompressedRdfTriples::load(&p) } {
+            Ok(triples) => Some(Changeset { path: p, triples }),
+            Err(e) => {
+                eprintln!("Error: unable to open {p:?}: {e:?}");
+                None
+            },
+        })
+        .collect();
+
+    if max_triples.is_none() && max_bytes.is_none() {
+        println!("generating queries by linearly replicating datasets...");
+        sparql::generate_linear_no_size_hint(
+            output,
+            decompressor,
+            datasets
+                .iter()
+                .map(|dataset| dataset.path.display().to_string())
+                .zip(rdf::triple_generator::linear_changeset_triple_generator(&datasets)),
+            update_kind,
+        )?;
+
+        return Ok(());
+    }
+
+    println!("generating bounded size queries by replicating datasets...");
+    sparql::generate_linear_no_size_hint(
+        output,
+        decompressor,
+        rdf::triple_generator::bounded_changeset_triple_generator(
+            &datasets,
+            max_triples,
+            max_bytes.map(|max_bytes| update_kind.max_body_len(max_bytes)),
+            merge,
+            |triple| sparql::rendered_triple_len(decompressor, triple),
+        ),
+        update_kind,
+    )?;
+
+    Ok(())
+}
+
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let opts: Opts = Opts::parse();
 
@@ -229,6 +969,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             compressor_state_out,
             recursive,
             dedup,
+            canonicalize,
             datasets,
         } => {
             let compressor_state_out = compressor_state_out.as_ref().unwrap_or_else(|| {
@@ -249,7 +990,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 let dataset = dataset?;
 
                 println!("compressing {:?}...", dataset);
-                compressor.compress_rdf_triple_file(dataset, dedup)?;
+                compressor.compress_rdf_triple_file(dataset, dedup, canonicalize)?;
             }
 
             println!("saving compressor state...");
@@ -258,140 +999,169 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         Opts::Generate {
             compressor_state,
             compressed_dataset,
-            query_out,
+            output,
+            query_spec_file,
             query_specs,
             g_type,
             output_order,
-            append,
+            update_kind,
+            state_tracking,
+            overlap,
+            overlap_with,
+            misses,
+            miss_dataset,
+            excluded_datasets,
+            max_bytes,
+            seed,
         } => {
             println!("loading compressor state...");
-            let decompressor = unsafe { RdfTripleDecompressor::load_state(compressor_state)? };
+            let decompressor = unsafe { RdfTripleDecompressor::load_state(&compressor_state)? };
+            let dataset_triples = load_main_dataset(&compressed_dataset)?;
 
-            println!("loading main dataset...");
-            let dataset_triples = unsafe { CompressedRdfTriples::load(compressed_dataset)? };
-            assert!(
-                dataset_triples.is_sorted(),
-                "dataset triples must be compressed with -D to ensure correct query generation"
-            );
+            let job = GenerateJob {
+                output: output.into_query_output(&decompressor),
+                query_specs,
+                query_spec_file,
+                g_type,
+                output_order,
+                update_kind,
+                state_tracking,
+                overlap,
+                overlap_with,
+                misses,
+                miss_dataset,
+                excluded_datasets,
+                max_bytes: max_bytes.map(|max_bytes| max_bytes as usize),
+                seed,
+                workload_file: None,
+            };
 
-            println!("loaded {} distinct triples from main dataset", dataset_triples.len());
+            generate(&compressor_state, &compressed_dataset, &decompressor, &dataset_triples, job)?;
+        },
+        Opts::Replicate {
+            compressor_state,
+            output,
+            recursive,
+            update_kind,
+            max_triples,
+            max_bytes,
+            merge,
+            compressed_datasets,
+        } => {
+            println!("loading compressor state...");
+            let decompressor = unsafe { RdfTripleDecompressor::load_state(compressor_state)? };
 
-            let query_specs: Vec<_> = query_specs
-                .into_iter()
-                .map(|QuerySpecOpt { n_queries, n_triples_per_query }| sparql::QuerySpec {
-                    n_queries,
-                    n_triples_per_query: n_triples_per_query.get_absolute(dataset_triples.len()),
-                })
-                .collect();
+            let limits = ReplicateLimits {
+                max_triples: max_triples.map(|max_triples| max_triples as usize),
+                max_bytes: max_bytes.map(|max_bytes| max_bytes as usize),
+                merge,
+            };
 
-            match g_type {
-                GenerateType::Changeset { compressed_changesets: compressed_changeset_dir, generate_type } => {
-                    let changesets: Vec<_> =
-                        changeset_file_iter(compressed_changeset_dir, COMPRESSED_TRIPLE_FILE_EXTENSION)
-                            .map(Result::unwrap)
-                            .filter_map(|de| match unsafe { CompressedRdfTriples::load(de.path()) } {
-                                Ok(triples) => Some(triples),
-                                Err(e) => {
-                                    eprintln!("Error: unable to open {:?}: {e:?}", de.path());
-                                    None
-                                },
-                            })
-                            .collect();
-
-                    match generate_type {
-                        GenerateChangesetType::AsIs => {
-                            println!("generating queries from changesets...");
-
-                            sparql::generate_queries(
-                                query_out,
-                                query_specs,
-                                &decompressor,
-                                rdf::triple_generator::as_is_changeset_triple_generator(&changesets),
-                                output_order,
-                                append,
-                            )
-                        },
-                        GenerateChangesetType::FixedSize => {
-                            println!("generating fixed size queries from changesets...");
-
-                            sparql::generate_queries(
-                                query_out,
-                                query_specs,
-                                &decompressor,
-                                rdf::triple_generator::fixed_size_changeset_triple_generator(
-                                    &changesets,
-                                    &dataset_triples,
-                                ),
-                                output_order,
-                                append,
-                            )
-                        },
-                    }
-                },
-                GenerateType::Randomized { allow_duplicates: false } => {
-                    println!("generating distinct queries from main dataset...");
-
-                    let total_query_triples: usize = query_specs
-                        .iter()
-                        .map(|sparql::QuerySpec { n_queries, n_triples_per_query }| n_queries * n_triples_per_query)
-                        .sum();
-
-                    sparql::generate_queries(
-                        query_out,
-                        query_specs,
-                        &decompressor,
-                        rdf::triple_generator::random_distinct_triple_generator(&dataset_triples, total_query_triples),
-                        output_order,
-                        append,
-                    )
-                },
-                GenerateType::Randomized { allow_duplicates: true } => {
-                    println!("generating queries from main dataset...");
-
-                    sparql::generate_queries(
-                        query_out,
-                        query_specs,
-                        &decompressor,
-                        rdf::triple_generator::random_triple_generator(&dataset_triples),
-                        output_order,
-                        append,
-                    )
-                },
-            }?
+            replicate(
+                &decompressor,
+                compressed_datasets,
+                recursive,
+                &output.into_query_output(&decompressor),
+                update_kind,
+                limits,
+            )?;
         },
-        Opts::Replicate { compressor_state, query_out, recursive, append, compressed_datasets } => {
+        Opts::Replay { compressor_state, output, mode, compressed_changesets } => {
             println!("loading compressor state...");
             let decompressor = unsafe { RdfTripleDecompressor::load_state(compressor_state)? };
 
-            println!("loading datasets to replicate...");
-            let datasets: Vec<_> = dataset_iter(compressed_datasets, recursive, COMPRESSED_TRIPLE_FILE_EXTENSION)
-                .map(Result::unwrap)
-                .filter_map(|p| match unsafe { CompressedRdfTriples::load(&p) } {
-                    Ok(triples) => Some(triples),
-                    Err(e) => {
-                        eprintln!("Error: unable to open {p:?}: {e:?}");
-                        None
-                    },
-                })
-                .collect();
+            println!("finding changesets...");
+            let changesets = changeset_pairs(compressed_changesets, COMPRESSED_TRIPLE_FILE_EXTENSION)?;
 
-            println!("generating queries by linearly replicating datasets...");
-            sparql::generate_linear_no_size_hint(
-                query_out,
+            println!("generating queries by replaying changesets...");
+            sparql::replay::generate_replay_queries(
+                &output.into_query_output(&decompressor),
                 &decompressor,
-                rdf::triple_generator::linear_changeset_triple_generator(&datasets),
-                append,
+                &changesets,
+                mode,
             )?;
         },
-        Opts::Decompress { compressor_state, recursive, compressed_datasets } => {
+        Opts::RunWorkload { workload } => {
+            let Workload { compressor_state, main_dataset, seed, phases } = Workload::load(&workload)?;
+
+            println!("loading compressor state...");
+            let decompressor = unsafe { RdfTripleDecompressor::load_state(&compressor_state)? };
+            let dataset_triples = match &main_dataset {
+                Some(path) if phases.iter().any(|phase| phase.generator.is_some()) => Some(load_main_dataset(path)?),
+                _ => None,
+            };
+
+            // the seeds of all phases are drawn up front, so that giving one phase its own seed
+            // does not change the seeds of the others
+            let mut seed_rng = seed.map(SmallRng::seed_from_u64);
+            let derived_seeds: Vec<Option<u64>> =
+                phases.iter().map(|_| seed_rng.as_mut().map(|rng| rng.gen())).collect();
+
+            let n_phases = phases.len();
+
+            for ((ix, phase), derived_seed) in phases.into_iter().enumerate().zip(derived_seeds) {
+                println!("running {} ({}/{n_phases})...", phase.display_name(ix), ix + 1);
+
+                let output = phase.output.into_query_output(&decompressor);
+
+                match (phase.generator, phase.replicate) {
+                    (Some(g_type), None) => {
+                        let job = GenerateJob {
+                            output,
+                            query_specs: phase.query_specs,
+                            query_spec_file: phase.query_spec_file,
+                            g_type,
+                            output_order: phase.output_order.unwrap_or_default(),
+                            update_kind: phase.update_kind,
+                            state_tracking: phase.state_tracking.unwrap_or_default(),
+                            overlap: phase.overlap,
+                            overlap_with: phase.overlap_with.unwrap_or_default(),
+                            misses: phase.misses,
+                            miss_dataset: phase.miss_dataset,
+                            excluded_datasets: phase.exclude,
+                            max_bytes: phase.max_bytes,
+                            seed: phase.seed.or(derived_seed),
+                            workload_file: Some(workload.clone()),
+                        };
+
+                        generate(
+                            &compressor_state,
+                            main_dataset.as_ref().expect("main dataset for generator phases"),
+                            &decompressor,
+                            dataset_triples
+                                .as_ref()
+                                .expect("main dataset to be loaded for generator phases"),
+                            job,
+                        )?;
+                    },
+                    (None, Some(ReplicateSource { datasets, recursive, max_triples, max_bytes, merge })) => {
+                        let limits = ReplicateLimits { max_triples, max_bytes, merge };
+                        replicate(&decompressor, datasets, recursive, &output, phase.update_kind, limits)?;
+                    },
+                    _ => unreachable!("phases are validated when loading the workload"),
+                }
+            }
+        },
+        Opts::Decompress {
+            compressor_state,
+            recursive,
+            format,
+            prefixes,
+            learn_prefixes,
+            graph,
+            compressed_datasets,
+        } => {
             println!("loading compressor state...");
             let decompressor = unsafe { RdfTripleDecompressor::load_state(compressor_state)? };
 
+            let prefix_map =
+                PrefixMap::from_declarations(prefixes, decompressor.terms().map(|(_, term)| term), learn_prefixes);
+
             for dataset in dataset_iter(compressed_datasets, recursive, COMPRESSED_TRIPLE_FILE_EXTENSION) {
                 let dataset = dataset?;
 
                 println!("decompressing {dataset:?}...");
-                decompressor.decompress_rdf_triple_file(dataset)?;
+                decompressor.decompress_rdf_triple_file(dataset, format, &prefix_map, graph.as_deref())?;
             }
         },
         Opts::Stats { recursive, compressed_datasets } => {
@@ -458,6 +1228,105 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 }
             }
         },
+        Opts::Lookup {
+            compressor_state,
+            canonicalize,
+            terms,
+            ids,
+            triples,
+            recursive,
+            compressed_datasets,
+        } => {
+            println!("loading compressor state...");
+            let decompressor = unsafe { RdfTripleDecompressor::load_state(compressor_state)? };
+
+            for term in terms {
+                // IRIs have a separate id as predicate
+                let positions: &[_] = match NTriplesTerm::parse(&term) {
+                    Some(NTriplesTerm::Iri(_)) => &[TermPosition::SubjectOrObject, TermPosition::Predicate],
+                    _ => &[TermPosition::SubjectOrObject],
+                };
+
+                for &position in positions {
+                    let id = RdfTripleCompressor::rdf_term_id(&term, position, canonicalize)?;
+                    let label = match position {
+                        TermPosition::SubjectOrObject => "",
+                        TermPosition::Predicate => " (as predicate)",
+                    };
+
+                    match decompressor.decompress_term(id) {
+                        Some(stored) => println!("{term}{label} -> {id} (stored as {stored})"),
+                        None => println!("{term}{label} -> {id} (not in compressor state)"),
+                    }
+                }
+            }
+
+            for id in ids {
+                match decompressor.decompress_term(id) {
+                    Some(term) => println!("{id} -> {term}"),
+                    None => println!("{id} -> unknown id"),
+                }
+            }
+
+            let triple_ids: Vec<_> = triples
+                .iter()
+                .map(|triple| RdfTripleCompressor::rdf_triple_ids(triple, canonicalize))
+                .collect::<Result<Vec<_>, _>>()?
+                .into_iter()
+                .flatten()
+                .collect();
+
+            for triple in &triple_ids {
+                match decompressor.decompress_rdf_triple(triple) {
+                    Some([s, p, o]) => println!("{triple:?} -> {s} {p} {o} ."),
+                    None => println!("{triple:?} -> at least one term is not in compressor state"),
+                }
+            }
+
+            if triple_ids.is_empty() {
+                return Ok(());
+            }
+
+            for path in dataset_iter(compressed_datasets, recursive, COMPRESSED_TRIPLE_FILE_EXTENSION) {
+                let path = path?;
+                match unsafe { CompressedRdfTriples::load(&path) } {
+                    Ok(dataset) => {
+                        let is_sorted = dataset.is_sorted();
+
+                        for triple in &triple_ids {
+                            let contained = if is_sorted {
+                                dataset.contains(triple)
+                            } else {
+                                dataset.iter().any(|t| t == triple)
+                            };
+
+                            if contained {
+                                println!("{triple:?} is contained in {path:?}");
+                            } else {
+                                println!("{triple:?} is not contained in {path:?}");
+                            }
+                        }
+                    },
+                    Err(e) => eprintln!("Error: unable to open {path:?}: {e:?}; skipping"),
+                }
+            }
+        },
+        Opts::ExportDictionary { compressor_state, dictionary_out, format } => {
+            println!("loading compressor state...");
+            let decompressor = unsafe { RdfTripleDecompressor::load_state(compressor_state)? };
+
+            println!("exporting dictionary...");
+            let mut writer = BufWriter::new(File::create(dictionary_out)?);
+            export_dictionary(&decompressor, &mut writer, format)?;
+        },
+        Opts::ImportDictionary { dictionary, compressor_state_out, format, verify_ids } => {
+            println!("importing dictionary...");
+            let reader = BufReader::new(File::open(dictionary)?);
+            let mut compressor = import_dictionary(reader, format, verify_ids)?;
+
+            println!("saving compressor state...");
+            compressor.save_state(compressor_state_out)?;
+        },
     }
 
     Ok(())
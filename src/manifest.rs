@@ -0,0 +1,124 @@
+//! Reproducibility manifests written next to generated queries.
+//!
+//! A manifest records everything needed to regenerate a query file byte for byte:
+//! the seed, the command line, fingerprints of all input files and the resolved query specs.
+
+use crate::{sparql::QuerySpec, util::with_file_name_suffix};
+use serde_json::{json, Value};
+use std::{
+    fs::File,
+    hash::Hasher,
+    io::{self, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+};
+
+pub const MANIFEST_FILE_SUFFIX: &str = ".manifest.jsonl";
+
+/// 64 bit FNV-1a, used instead of ahash because fingerprints must be comparable between machines
+struct Fnv1aHasher(u64);
+
+impl Default for Fnv1aHasher {
+    fn default() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Hasher for Fnv1aHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.0 = (self.0 ^ b as u64).wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+}
+
+fn hash_file_into<P: AsRef<Path>>(path: P, hasher: &mut Fnv1aHasher) -> io::Result<()> {
+    let mut f = File::open(path)?;
+    let mut buf = vec![0; 1 << 20];
+
+    loop {
+        match f.read(&mut buf)? {
+            0 => return Ok(()),
+            n => hasher.write(&buf[..n]),
+        }
+    }
+}
+
+/// Path of the manifest belonging to the query file at `query_out`, i.e. `query_out` with [`MANIFEST_FILE_SUFFIX`] appended
+pub fn manifest_path<P: AsRef<Path>>(query_out: P) -> PathBuf {
+    with_file_name_suffix(query_out, MANIFEST_FILE_SUFFIX)
+}
+
+pub struct Manifest {
+    seed: u64,
+    inputs: Vec<(&'static str, Vec<PathBuf>)>,
+}
+
+impl Manifest {
+    pub fn new(seed: u64) -> Self {
+        Self { seed, inputs: Vec::new() }
+    }
+
+    /// Records an input of the generation, `paths` are fingerprinted together in the given order
+    pub fn add_input<I: IntoIterator<Item = PathBuf>>(&mut self, role: &'static str, paths: I) {
+        self.inputs.push((role, paths.into_iter().collect()));
+    }
+
+    /// Writes the manifest as a single JSON line to the manifest path of `query_out`.
+    ///
+    /// With `append` the line is appended, so that a query file built by several runs
+    /// has one manifest record per run.
+    pub fn write<P: AsRef<Path>>(&self, query_out: P, append: bool, query_specs: &[QuerySpec]) -> io::Result<()> {
+        let inputs = self
+            .inputs
+            .iter()
+            .map(|(role, paths)| {
+                let mut hasher = Fnv1aHasher::default();
+                for path in paths {
+                    hash_file_into(path, &mut hasher)?;
+                }
+
+                let paths: Vec<_> = paths.iter().map(|path| path.to_string_lossy()).collect();
+
+                Ok(json!({
+                    "role": role,
+                    "paths": paths,
+                    "fingerprint": format!("{:016x}", hasher.finish()),
+                }))
+            })
+            .collect::<io::Result<Vec<Value>>>()?;
+
+        let query_specs: Vec<_> = query_specs
+            .iter()
+            .map(|spec| {
+                json!({
+                    "description": spec.description,
+                    "n_queries": spec.n_queries(),
+                    "total_size": spec.total_size(),
+                })
+            })
+            .collect();
+
+        let record = json!({
+            "version": env!("CARGO_PKG_VERSION"),
+            "seed": self.seed,
+            "arguments": std::env::args().collect::<Vec<_>>(),
+            "inputs": inputs,
+            "query_specs": query_specs,
+        });
+
+        let f = File::options()
+            .append(append)
+            .truncate(!append)
+            .create(true)
+            .write(true)
+            .open(manifest_path(query_out))?;
+
+        let mut writer = BufWriter::new(f);
+        writeln!(writer, "{record}")?;
+        writer.flush()
+    }
+}
@@ -0,0 +1,311 @@
+//! Query specs of the form `<N_QUERIES>x<SIZE>` and their resolution into concrete query sizes.
+//!
+//! `<SIZE>` is one of
+//! - a single value, e.g. `100x50`
+//! - an inclusive range, sizes are drawn uniformly from it, e.g. `100x10-1000`
+//! - a distribution: `uniform(<MIN>,<MAX>)`, `normal(<MEAN>,<STD_DEV>)`,
+//!   `lognormal(<MEDIAN>,<SIGMA>)` or `zipf(<MAX>,<EXPONENT>)`
+//!
+//! Every value can be absolute, a percentage of the main dataset (`1.5%`)
+//! or a percentage of all changesets together (`1.5%c`).
+
+use crate::sparql::QuerySpec;
+use rand::Rng;
+use rand_distr::{Distribution, LogNormal, Normal, Zipf};
+use serde::Deserialize;
+use std::{path::Path, str::FromStr};
+
+#[derive(Clone, Copy)]
+pub enum SizeReference {
+    /// the main dataset (or its patterns for pattern queries)
+    MainDataset,
+
+    /// all changesets together
+    Changesets,
+}
+
+#[derive(Clone, Copy)]
+pub enum SizeValue {
+    Absolute(usize),
+
+    /// fraction of the size of the reference
+    Relative(f64, SizeReference),
+}
+
+#[derive(Clone, Copy)]
+pub enum QuerySizeOpt {
+    Fixed(SizeValue),
+
+    /// uniformly distributed within the inclusive range
+    Uniform(SizeValue, SizeValue),
+
+    Normal {
+        mean: SizeValue,
+        std_dev: SizeValue,
+    },
+
+    /// log-normal distribution, `sigma` is the standard deviation of the logarithm of the size
+    LogNormal {
+        median: SizeValue,
+        sigma: f64,
+    },
+
+    /// sizes in `1..=max` where size `k` has a probability proportional to `k^-exponent`
+    Zipf {
+        max: SizeValue,
+        exponent: f64,
+    },
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(try_from = "String")]
+pub struct QuerySpecOpt {
+    description: String,
+    n_queries: usize,
+    size: QuerySizeOpt,
+}
+
+/// The sizes relative sizes refer to
+pub struct SizeReferences {
+    pub main_dataset: usize,
+
+    /// total number of triples of all changesets, if changesets are used
+    pub changesets: Option<usize>,
+}
+
+impl FromStr for SizeValue {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+
+        let (percentage, reference) = if let Some(percentage) = s.strip_suffix("%c") {
+            (percentage, SizeReference::Changesets)
+        } else if let Some(percentage) = s.strip_suffix('%') {
+            (percentage, SizeReference::MainDataset)
+        } else {
+            return s
+                .parse()
+                .map(SizeValue::Absolute)
+                .map_err(|e| format!("invalid query spec, size {s:?} is not integer: {e:?}"));
+        };
+
+        let percentage: f64 = percentage
+            .parse()
+            .map_err(|e| format!("invalid query spec, percentage {s:?} is not a number: {e:?}"))?;
+
+        if !percentage.is_finite() || percentage < 0.0 {
+            return Err(format!("invalid query spec, percentage {s:?} must be finite and not negative"));
+        }
+
+        Ok(SizeValue::Relative(percentage / 100.0, reference))
+    }
+}
+
+fn parse_parameter(s: &str) -> Result<f64, String> {
+    let s = s.trim();
+
+    s.parse::<f64>()
+        .ok()
+        .filter(|p| p.is_finite())
+        .ok_or_else(|| format!("invalid query spec, parameter {s:?} is not a finite number"))
+}
+
+impl FromStr for QuerySizeOpt {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+
+        if let Some((distribution, parameters)) = s.strip_suffix(')').and_then(|s| s.split_once('(')) {
+            let Some((a, b)) = parameters.split_once(',') else {
+                return Err(format!("invalid query spec, {distribution} expects two parameters"));
+            };
+
+            return match distribution.trim() {
+                "uniform" => Ok(QuerySizeOpt::Uniform(a.parse()?, b.parse()?)),
+                "normal" => Ok(QuerySizeOpt::Normal { mean: a.parse()?, std_dev: b.parse()? }),
+                "lognormal" => Ok(QuerySizeOpt::LogNormal { median: a.parse()?, sigma: parse_parameter(b)? }),
+                "zipf" => Ok(QuerySizeOpt::Zipf { max: a.parse()?, exponent: parse_parameter(b)? }),
+                other => Err(format!(
+                    "invalid query spec, unknown distribution {other:?}, expected uniform, normal, lognormal or zipf"
+                )),
+            };
+        }
+
+        match s.split_once('-') {
+            Some((min, max)) => Ok(QuerySizeOpt::Uniform(min.parse()?, max.parse()?)),
+            None => Ok(QuerySizeOpt::Fixed(s.parse()?)),
+        }
+    }
+}
+
+impl FromStr for QuerySpecOpt {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (n_queries, size) = s
+            .split_once('x')
+            .ok_or_else(|| "invalid query spec, expected delimiter".to_owned())?;
+
+        let n_queries = n_queries
+            .trim()
+            .parse()
+            .map_err(|e| format!("invalid query spec, first value is not integer: {e:?}"))?;
+
+        Ok(QuerySpecOpt { description: s.trim().to_owned(), n_queries, size: size.parse()? })
+    }
+}
+
+impl TryFrom<String> for QuerySpecOpt {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl SizeValue {
+    pub fn get_absolute(self, references: &SizeReferences) -> Result<usize, String> {
+        match self {
+            SizeValue::Absolute(n) => Ok(n),
+            SizeValue::Relative(fraction, SizeReference::MainDataset) => {
+                Ok((references.main_dataset as f64 * fraction) as usize)
+            },
+            SizeValue::Relative(fraction, SizeReference::Changesets) => references
+                .changesets
+                .map(|n_total| (n_total as f64 * fraction) as usize)
+                .ok_or_else(|| "sizes relative to the changesets (%c) require the changeset generator".to_owned()),
+        }
+    }
+}
+
+/// Rounds a sampled size, sampled sizes are at least 1 so that no empty queries are generated
+fn sampled_size(size: f64) -> usize {
+    size.round().max(1.0) as usize
+}
+
+impl QuerySpecOpt {
+    /// Resolves relative sizes against `references` and draws the size of every query of the spec from `rng`
+    pub fn resolve<R: Rng>(&self, references: &SizeReferences, rng: &mut R) -> Result<QuerySpec, String> {
+        let n = self.n_queries;
+
+        let query_sizes = match self.size {
+            QuerySizeOpt::Fixed(size) => vec![size.get_absolute(references)?; n],
+            QuerySizeOpt::Uniform(min, max) => {
+                let (min, max) = (min.get_absolute(references)?, max.get_absolute(references)?);
+
+                if min > max {
+                    return Err(format!("query spec {}: empty size range {min}-{max}", self.description));
+                }
+
+                (0..n).map(|_| rng.gen_range(min..=max)).collect()
+            },
+            QuerySizeOpt::Normal { mean, std_dev } => {
+                let mean = mean.get_absolute(references)? as f64;
+                let std_dev = std_dev.get_absolute(references)? as f64;
+
+                let normal = Normal::new(mean, std_dev)
+                    .map_err(|e| format!("query spec {}: invalid normal distribution: {e}", self.description))?;
+
+                (0..n).map(|_| sampled_size(normal.sample(rng))).collect()
+            },
+            QuerySizeOpt::LogNormal { median, sigma } => {
+                let median = median.get_absolute(references)?;
+
+                if median == 0 {
+                    return Err(format!("query spec {}: median of lognormal must be positive", self.description));
+                }
+
+                let log_normal = LogNormal::new((median as f64).ln(), sigma)
+                    .map_err(|e| format!("query spec {}: invalid lognormal distribution: {e}", self.description))?;
+
+                (0..n).map(|_| sampled_size(log_normal.sample(rng))).collect()
+            },
+            QuerySizeOpt::Zipf { max, exponent } => {
+                let zipf = Zipf::new(max.get_absolute(references)? as u64, exponent)
+                    .map_err(|e| format!("query spec {}: invalid zipf distribution: {e}", self.description))?;
+
+                (0..n).map(|_| sampled_size(zipf.sample(rng))).collect()
+            },
+        };
+
+        Ok(QuerySpec { description: self.description.clone(), query_sizes })
+    }
+}
+
+/// Reads query specs from `path`, one spec per line. Empty lines and lines starting with `#` are ignored
+pub fn load_query_specs<P: AsRef<Path>>(path: P) -> Result<Vec<QuerySpecOpt>, Box<dyn std::error::Error>> {
+    let path = path.as_ref();
+    let mut specs = Vec::new();
+
+    for (line_no, line) in std::fs::read_to_string(path)?.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        specs.push(line.parse().map_err(|e| format!("{path:?} line {}: {e}", line_no + 1))?);
+    }
+
+    Ok(specs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::SmallRng, SeedableRng};
+
+    const REFERENCES: SizeReferences = SizeReferences { main_dataset: 1000, changesets: Some(200) };
+
+    fn sizes(spec: &str, references: &SizeReferences) -> Result<Vec<usize>, String> {
+        let mut rng = SmallRng::seed_from_u64(0);
+        Ok(spec.parse::<QuerySpecOpt>()?.resolve(references, &mut rng)?.query_sizes)
+    }
+
+    #[test]
+    fn fixed_and_relative_sizes() {
+        assert_eq!(sizes("3x10", &REFERENCES), Ok(vec![10, 10, 10]));
+        assert_eq!(sizes(" 2 x 1.5% ", &REFERENCES), Ok(vec![15, 15]));
+        assert_eq!(sizes("1x10%c", &REFERENCES), Ok(vec![20]));
+
+        let without_changesets = SizeReferences { main_dataset: 1000, changesets: None };
+        assert!(sizes("1x10%c", &without_changesets).unwrap_err().contains("(%c) require the changeset generator"));
+    }
+
+    #[test]
+    fn ranges_are_inclusive() {
+        let drawn = sizes("100x5-7", &REFERENCES).unwrap();
+        assert!(drawn.iter().all(|size| (5..=7).contains(size)));
+        assert!([5, 6, 7].iter().all(|size| drawn.contains(size)));
+
+        assert!(sizes("100x1%c-1%", &REFERENCES).unwrap().iter().all(|size| (2..=10).contains(size)));
+        assert!(sizes("1x7-5", &REFERENCES).unwrap_err().ends_with("empty size range 7-5"));
+    }
+
+    #[test]
+    fn distributions() {
+        assert!(sizes("100xuniform(1, 3)", &REFERENCES).unwrap().iter().all(|size| (1..=3).contains(size)));
+        assert!(sizes("100xzipf(5,1.5)", &REFERENCES).unwrap().iter().all(|size| (1..=5).contains(size)));
+        assert_eq!(sizes("3xnormal(5%,0)", &REFERENCES), Ok(vec![50; 3]));
+        assert_eq!(sizes("3xlognormal(20,0)", &REFERENCES), Ok(vec![20; 3]));
+
+        // sampled sizes are at least 1
+        assert_eq!(sizes("3xnormal(0,0)", &REFERENCES), Ok(vec![1; 3]));
+    }
+
+    #[test]
+    fn invalid_specs() {
+        let err = |spec: &str| sizes(spec, &REFERENCES).unwrap_err();
+
+        assert_eq!(err("10"), "invalid query spec, expected delimiter");
+        assert!(err("ax10").starts_with("invalid query spec, first value is not integer"));
+        assert!(err("1xten").starts_with("invalid query spec, size \"ten\" is not integer"));
+        assert!(err("1xten%").starts_with("invalid query spec, percentage \"ten%\" is not a number"));
+        assert!(err("1xinf%").ends_with("must be finite and not negative"));
+        assert_eq!(err("1xuniform(1)"), "invalid query spec, uniform expects two parameters");
+        assert!(err("1xpareto(1,2)").starts_with("invalid query spec, unknown distribution \"pareto\""));
+        assert!(err("1xzipf(10,x)").starts_with("invalid query spec, parameter \"x\" is not a finite number"));
+        assert_eq!(err("1xlognormal(0,1)"), "query spec 1xlognormal(0,1): median of lognormal must be positive");
+    }
+}
@@ -0,0 +1,563 @@
+//! Canonicalization of IRIs, language tags and literal lexical forms.
+//!
+//! Terms that are equal in value but differ in their lexical representation
+//! (e.g. `"01"^^xsd:integer` and `"1"^^xsd:integer`) would otherwise be interned as distinct terms.
+//! Every function in here returns its input unchanged if it does not know how to canonicalize it
+//! or if the input is not a valid lexical form.
+
+use std::borrow::Cow;
+
+const XSD: &str = "http://www.w3.org/2001/XMLSchema#";
+
+const XSD_INTEGER_TYPES: &[&str] = &[
+    "integer",
+    "nonPositiveInteger",
+    "negativeInteger",
+    "long",
+    "int",
+    "short",
+    "byte",
+    "nonNegativeInteger",
+    "unsignedLong",
+    "unsignedInt",
+    "unsignedShort",
+    "unsignedByte",
+    "positiveInteger",
+];
+
+/// Canonicalizes the lexical form `value` of a literal with the datatype IRI `datatype`.
+///
+/// Supports the xsd integer types, `xsd:decimal`, `xsd:double`, `xsd:float`, `xsd:boolean`,
+/// `xsd:dateTime` and `xsd:dateTimeStamp`.
+pub fn canonicalize_lexical_form<'a>(value: &'a str, datatype: &str) -> Cow<'a, str> {
+    let Some(local_name) = datatype.strip_prefix(XSD) else {
+        return Cow::Borrowed(value);
+    };
+
+    let canonical = match local_name {
+        t if XSD_INTEGER_TYPES.contains(&t) => canonical_integer(value.trim()),
+        "decimal" => canonical_decimal(value.trim()),
+        "double" => canonical_floating_point::<f64>(value.trim()),
+        "float" => canonical_floating_point::<f32>(value.trim()),
+        "boolean" => canonical_boolean(value.trim()),
+        "dateTime" | "dateTimeStamp" => canonical_date_time(value.trim()),
+        _ => None,
+    };
+
+    match canonical {
+        Some(canonical) if canonical != value => Cow::Owned(canonical),
+        _ => Cow::Borrowed(value),
+    }
+}
+
+/// Canonicalizes a language tag by lowercasing it, language tags are compared case-insensitively
+pub fn canonicalize_language_tag(tag: &str) -> Cow<'_, str> {
+    if tag.bytes().any(|b| b.is_ascii_uppercase()) {
+        Cow::Owned(tag.to_ascii_lowercase())
+    } else {
+        Cow::Borrowed(tag)
+    }
+}
+
+/// Applies syntax-based normalization (RFC 3986 section 6.2.2) and default port removal to an absolute IRI.
+///
+/// That is: lowercasing scheme and host, uppercasing percent-encoding hex digits,
+/// decoding percent-encoded unreserved characters, removing dot-segments from the path
+/// and removing the port if it is the default port for `http` or `https`.
+pub fn canonicalize_iri(iri: &str) -> Cow<'_, str> {
+    let Some((scheme, rest)) = iri.split_once(':') else {
+        return Cow::Borrowed(iri);
+    };
+
+    if scheme.is_empty()
+        || !scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+        || !scheme.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+    {
+        return Cow::Borrowed(iri);
+    }
+
+    let scheme = scheme.to_ascii_lowercase();
+
+    let (rest, fragment) = match rest.split_once('#') {
+        Some((rest, fragment)) => (rest, Some(fragment)),
+        None => (rest, None),
+    };
+
+    let (rest, query) = match rest.split_once('?') {
+        Some((rest, query)) => (rest, Some(query)),
+        None => (rest, None),
+    };
+
+    let mut canonical = String::with_capacity(iri.len());
+    canonical.push_str(&scheme);
+    canonical.push(':');
+
+    let path = if let Some(rest) = rest.strip_prefix("//") {
+        let (authority, path) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+
+        let (userinfo, host_port) = match authority.rsplit_once('@') {
+            Some((userinfo, host_port)) => (Some(userinfo), host_port),
+            None => (None, authority),
+        };
+
+        // the port is everything after the last colon, unless that colon is inside an IP literal
+        let (host, port) = match host_port.rsplit_once(':') {
+            Some((host, port)) if !port.contains(']') => (host, Some(port)),
+            _ => (host_port, None),
+        };
+
+        let port = match (scheme.as_str(), port) {
+            (_, Some("")) | ("http", Some("80")) | ("https", Some("443")) => None,
+            (_, port) => port,
+        };
+
+        canonical.push_str("//");
+
+        if let Some(userinfo) = userinfo {
+            push_percent_normalized(&mut canonical, userinfo);
+            canonical.push('@');
+        }
+
+        // decode first so that decoded characters are lowercased too,
+        // the second pass restores the uppercase hex digits of the remaining percent-encodings
+        let mut lowercase_host = String::with_capacity(host.len());
+        push_percent_normalized(&mut lowercase_host, host);
+        lowercase_host.make_ascii_lowercase();
+        push_percent_normalized(&mut canonical, &lowercase_host);
+
+        if let Some(port) = port {
+            canonical.push(':');
+            canonical.push_str(port);
+        }
+
+        path
+    } else {
+        rest
+    };
+
+    let mut normalized_path = String::with_capacity(path.len());
+    push_percent_normalized(&mut normalized_path, path);
+    canonical.push_str(&remove_dot_segments(&normalized_path));
+
+    if let Some(query) = query {
+        canonical.push('?');
+        push_percent_normalized(&mut canonical, query);
+    }
+
+    if let Some(fragment) = fragment {
+        canonical.push('#');
+        push_percent_normalized(&mut canonical, fragment);
+    }
+
+    if canonical == iri {
+        Cow::Borrowed(iri)
+    } else {
+        Cow::Owned(canonical)
+    }
+}
+
+fn hex_value(b: u8) -> Option<u8> {
+    match b {
+        b'0'..=b'9' => Some(b - b'0'),
+        b'a'..=b'f' => Some(b - b'a' + 10),
+        b'A'..=b'F' => Some(b - b'A' + 10),
+        _ => None,
+    }
+}
+
+fn is_unreserved(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || matches!(b, b'-' | b'.' | b'_' | b'~')
+}
+
+/// Appends `s` to `out`, decoding percent-encoded unreserved characters and uppercasing
+/// the hex digits of all other percent-encodings
+fn push_percent_normalized(out: &mut String, s: &str) {
+    let bytes = s.as_bytes();
+    let mut last_copied = 0;
+    let mut ix = 0;
+
+    while ix < bytes.len() {
+        if bytes[ix] == b'%' && ix + 2 < bytes.len() {
+            if let (Some(hi), Some(lo)) = (hex_value(bytes[ix + 1]), hex_value(bytes[ix + 2])) {
+                out.push_str(&s[last_copied..ix]);
+
+                let decoded = (hi << 4) | lo;
+                if is_unreserved(decoded) {
+                    out.push(decoded as char);
+                } else {
+                    out.push('%');
+                    out.push(bytes[ix + 1].to_ascii_uppercase() as char);
+                    out.push(bytes[ix + 2].to_ascii_uppercase() as char);
+                }
+
+                ix += 3;
+                last_copied = ix;
+                continue;
+            }
+        }
+
+        ix += 1;
+    }
+
+    out.push_str(&s[last_copied..]);
+}
+
+/// Removes `.` and `..` segments from an absolute path as described in RFC 3986 section 5.2.4
+fn remove_dot_segments(path: &str) -> Cow<'_, str> {
+    if !path.split('/').any(|segment| segment == "." || segment == "..") {
+        return Cow::Borrowed(path);
+    }
+
+    let mut output: Vec<&str> = Vec::new();
+    let segments: Vec<&str> = path.split('/').collect();
+
+    for (ix, &segment) in segments.iter().enumerate() {
+        let is_last = ix == segments.len() - 1;
+
+        match segment {
+            "." => {
+                if is_last {
+                    output.push("");
+                }
+            },
+            ".." => {
+                if output.len() > 1 || (output.len() == 1 && !output[0].is_empty()) {
+                    output.pop();
+                }
+
+                if is_last {
+                    output.push("");
+                }
+            },
+            segment => output.push(segment),
+        }
+    }
+
+    let mut joined = output.join("/");
+    if path.starts_with('/') && !joined.starts_with('/') {
+        joined.insert(0, '/');
+    }
+
+    Cow::Owned(joined)
+}
+
+fn split_sign(value: &str) -> (bool, &str) {
+    if let Some(rest) = value.strip_prefix('-') {
+        (true, rest)
+    } else {
+        (false, value.strip_prefix('+').unwrap_or(value))
+    }
+}
+
+fn canonical_integer(value: &str) -> Option<String> {
+    let (negative, digits) = split_sign(value);
+
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+
+    let digits = digits.trim_start_matches('0');
+
+    Some(match (negative, digits.is_empty()) {
+        (_, true) => "0".to_owned(),
+        (true, false) => format!("-{digits}"),
+        (false, false) => digits.to_owned(),
+    })
+}
+
+fn canonical_decimal(value: &str) -> Option<String> {
+    let (negative, unsigned) = split_sign(value);
+    let (int_part, frac_part) = unsigned.split_once('.').unwrap_or((unsigned, ""));
+
+    if (int_part.is_empty() && frac_part.is_empty())
+        || !int_part.bytes().all(|b| b.is_ascii_digit())
+        || !frac_part.bytes().all(|b| b.is_ascii_digit())
+    {
+        return None;
+    }
+
+    let int_part = match int_part.trim_start_matches('0') {
+        "" => "0",
+        int_part => int_part,
+    };
+
+    let frac_part = match frac_part.trim_end_matches('0') {
+        "" => "0",
+        frac_part => frac_part,
+    };
+
+    let is_zero = int_part == "0" && frac_part == "0";
+
+    Some(if negative && !is_zero {
+        format!("-{int_part}.{frac_part}")
+    } else {
+        format!("{int_part}.{frac_part}")
+    })
+}
+
+fn canonical_floating_point<F>(value: &str) -> Option<String>
+where
+    F: std::str::FromStr + std::fmt::UpperExp,
+{
+    match value {
+        "INF" | "+INF" => return Some("INF".to_owned()),
+        "-INF" => return Some("-INF".to_owned()),
+        "NaN" => return Some("NaN".to_owned()),
+        _ => (),
+    }
+
+    // rust's float parser also accepts things like "inf" or "infinity" which are not valid lexical forms
+    if !value
+        .bytes()
+        .all(|b| b.is_ascii_digit() || matches!(b, b'+' | b'-' | b'.' | b'e' | b'E'))
+    {
+        return None;
+    }
+
+    let parsed: F = value.parse().ok()?;
+    let formatted = format!("{parsed:E}");
+
+    let (mantissa, exponent) = formatted.split_once('E')?;
+    Some(if mantissa.contains('.') {
+        formatted
+    } else {
+        format!("{mantissa}.0E{exponent}")
+    })
+}
+
+fn canonical_boolean(value: &str) -> Option<String> {
+    match value {
+        "true" | "1" => Some("true".to_owned()),
+        "false" | "0" => Some("false".to_owned()),
+        _ => None,
+    }
+}
+
+fn is_leap_year(year: i64) -> bool {
+    year.rem_euclid(4) == 0 && (year.rem_euclid(100) != 0 || year.rem_euclid(400) == 0)
+}
+
+/// Number of days of the given month of the proleptic gregorian calendar
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Days since 1970-01-01 of the given proleptic gregorian date
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    era * 146097 + day_of_era - 719468
+}
+
+/// Inverse of [`days_from_civil`]
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    (year, month, day)
+}
+
+fn parse_fixed_digits(s: &str, n_digits: usize) -> Option<i64> {
+    if s.len() != n_digits || !s.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+
+    s.parse().ok()
+}
+
+/// Canonicalizes an `xsd:dateTime` by normalizing its timezone to `Z`
+/// and removing trailing zeros from the fractional seconds
+fn canonical_date_time(value: &str) -> Option<String> {
+    let (date, time) = value.split_once('T')?;
+
+    let (negative_year, date) = match date.strip_prefix('-') {
+        Some(date) => (true, date),
+        None => (false, date),
+    };
+
+    let mut date_parts = date.splitn(3, '-');
+    let year_str = date_parts.next()?;
+    if year_str.len() < 4 || (year_str.len() > 4 && year_str.starts_with('0')) {
+        return None;
+    }
+
+    let year = parse_fixed_digits(year_str, year_str.len())?;
+    let year = if negative_year { -year } else { year };
+    let month = parse_fixed_digits(date_parts.next()?, 2)?;
+    let day = parse_fixed_digits(date_parts.next()?, 2)?;
+
+    if !(1..=12).contains(&month) || !(1..=days_in_month(year, month)).contains(&day) {
+        return None;
+    }
+
+    let (time, tz_offset_minutes) = if let Some(time) = time.strip_suffix('Z') {
+        (time, Some(0))
+    } else if time.len() > 6 && matches!(time.as_bytes()[time.len() - 6], b'+' | b'-') {
+        let (time, tz) = time.split_at(time.len() - 6);
+        let (tz_hours, tz_minutes) = tz[1..].split_once(':')?;
+        let offset = parse_fixed_digits(tz_hours, 2)? * 60 + parse_fixed_digits(tz_minutes, 2)?;
+
+        if offset > 14 * 60 {
+            return None;
+        }
+
+        (time, Some(if tz.starts_with('-') { -offset } else { offset }))
+    } else {
+        (time, None)
+    };
+
+    let mut time_parts = time.splitn(3, ':');
+    let hour = parse_fixed_digits(time_parts.next()?, 2)?;
+    let minute = parse_fixed_digits(time_parts.next()?, 2)?;
+    let seconds = time_parts.next()?;
+
+    let (whole_seconds, fractional_seconds) = match seconds.split_once('.') {
+        Some((whole, frac)) if !frac.is_empty() && frac.bytes().all(|b| b.is_ascii_digit()) => (whole, frac),
+        Some(_) => return None,
+        None => (seconds, ""),
+    };
+
+    let second = parse_fixed_digits(whole_seconds, 2)?;
+    let fractional_seconds = fractional_seconds.trim_end_matches('0');
+
+    let is_end_of_day = hour == 24 && minute == 0 && second == 0 && fractional_seconds.is_empty();
+    if (hour > 23 && !is_end_of_day) || minute > 59 || second > 59 {
+        return None;
+    }
+
+    let total_minutes =
+        days_from_civil(year, month, day) * 24 * 60 + hour * 60 + minute - tz_offset_minutes.unwrap_or(0);
+
+    let (year, month, day) = civil_from_days(total_minutes.div_euclid(24 * 60));
+    let minute_of_day = total_minutes.rem_euclid(24 * 60);
+    let (hour, minute) = (minute_of_day / 60, minute_of_day % 60);
+
+    let sign = if year < 0 { "-" } else { "" };
+    let mut canonical = format!(
+        "{sign}{year:04}-{month:02}-{day:02}T{hour:02}:{minute:02}:{second:02}",
+        year = year.abs()
+    );
+
+    if !fractional_seconds.is_empty() {
+        canonical.push('.');
+        canonical.push_str(fractional_seconds);
+    }
+
+    if tz_offset_minutes.is_some() {
+        canonical.push('Z');
+    }
+
+    Some(canonical)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn xsd(value: &str, local_name: &str) -> String {
+        canonicalize_lexical_form(value, &format!("{XSD}{local_name}")).into_owned()
+    }
+
+    #[test]
+    fn iri_scheme_host_and_port() {
+        assert_eq!(canonicalize_iri("HTTP://Example.COM:80/a"), "http://example.com/a");
+        assert_eq!(canonicalize_iri("https://example.com:443/"), "https://example.com/");
+        assert_eq!(canonicalize_iri("http://example.com:8080/"), "http://example.com:8080/");
+        assert_eq!(canonicalize_iri("http://User@example.com/"), "http://User@example.com/");
+        assert!(matches!(canonicalize_iri("http://example.com/a"), Cow::Borrowed(_)));
+        assert!(matches!(canonicalize_iri("not an iri"), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn iri_dot_segments() {
+        assert_eq!(remove_dot_segments("/a/b/c/./../../g"), "/a/g");
+        assert_eq!(remove_dot_segments("mid/content=5/../6"), "mid/6");
+        assert_eq!(remove_dot_segments("/a/b/.."), "/a/");
+        assert_eq!(remove_dot_segments("/a/."), "/a/");
+        assert_eq!(remove_dot_segments("/../a"), "/a");
+        assert_eq!(canonicalize_iri("http://example.com/a/./b/../c?x=./..#."), "http://example.com/a/c?x=./..#.");
+    }
+
+    #[test]
+    fn iri_percent_encoding() {
+        assert_eq!(canonicalize_iri("http://example.com/%7euser/%2f"), "http://example.com/~user/%2F");
+        assert_eq!(canonicalize_iri("http://%41%62c.com/?q=%3d"), "http://abc.com/?q=%3D");
+        assert_eq!(canonicalize_iri("http://example.com/%zz%4"), "http://example.com/%zz%4");
+        assert_eq!(canonicalize_iri("http://example.com/%2E%2E/a"), "http://example.com/a");
+    }
+
+    #[test]
+    fn language_tags() {
+        assert_eq!(canonicalize_language_tag("en-US"), "en-us");
+        assert!(matches!(canonicalize_language_tag("de"), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn numeric_forms() {
+        assert_eq!(xsd("+007", "integer"), "7");
+        assert_eq!(xsd("-0", "int"), "0");
+        assert_eq!(xsd("-0012", "long"), "-12");
+        assert_eq!(xsd(" 5 ", "integer"), "5");
+        assert_eq!(xsd("1.5", "integer"), "1.5");
+
+        assert_eq!(xsd("01.500", "decimal"), "1.5");
+        assert_eq!(xsd("-0.0", "decimal"), "0.0");
+        assert_eq!(xsd(".5", "decimal"), "0.5");
+        assert_eq!(xsd("3", "decimal"), "3.0");
+        assert_eq!(xsd(".", "decimal"), ".");
+
+        assert_eq!(xsd("1e3", "double"), "1.0E3");
+        assert_eq!(xsd("0.015", "double"), "1.5E-2");
+        assert_eq!(xsd("+INF", "float"), "INF");
+        assert_eq!(xsd("inf", "double"), "inf");
+
+        assert_eq!(xsd("1", "boolean"), "true");
+        assert_eq!(xsd("yes", "boolean"), "yes");
+        assert_eq!(canonicalize_lexical_form("007", "http://example.com/integer"), "007");
+    }
+
+    #[test]
+    fn date_time_timezones() {
+        assert_eq!(xsd("2020-01-01T00:30:00+01:00", "dateTime"), "2019-12-31T23:30:00Z");
+        assert_eq!(xsd("2020-01-01T12:00:00.500-02:30", "dateTime"), "2020-01-01T14:30:00.5Z");
+        assert_eq!(xsd("2020-12-31T24:00:00Z", "dateTime"), "2021-01-01T00:00:00Z");
+        assert_eq!(xsd("2020-02-28T23:00:00-01:00", "dateTimeStamp"), "2020-02-29T00:00:00Z");
+        assert_eq!(xsd("-0001-01-01T00:00:00+00:00", "dateTime"), "-0001-01-01T00:00:00Z");
+        assert_eq!(xsd("2020-01-01T12:00:00.0", "dateTime"), "2020-01-01T12:00:00");
+        assert_eq!(xsd("2020-01-01T12:00:00+15:00", "dateTime"), "2020-01-01T12:00:00+15:00");
+    }
+
+    #[test]
+    fn date_time_invalid_days() {
+        for value in [
+            "2020-02-31T00:00:00Z",
+            "2021-02-29T00:00:00Z",
+            "1900-02-29T00:00:00Z",
+            "2020-04-31T00:00:00Z",
+            "2020-13-01T00:00:00Z",
+            "2020-01-00T00:00:00Z",
+            "2020-01-01T24:00:01Z",
+        ] {
+            assert_eq!(xsd(value, "dateTime"), value);
+        }
+
+        assert_eq!(xsd("2000-02-29T00:00:00Z", "dateTime"), "2000-02-29T00:00:00Z");
+        assert_eq!(xsd("2020-02-29T01:00:00+01:00", "dateTime"), "2020-02-29T00:00:00Z");
+    }
+}
@@ -0,0 +1,296 @@
+//! Serialization of decompressed triples into different RDF formats
+
+use crate::rdf::{
+    prefix::PrefixMap,
+    term::{unescape, NTriplesTerm},
+};
+use clap::ArgEnum;
+use serde_json::{Map, Value};
+use std::io::{self, Write};
+
+pub const RDF_TYPE: &str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#type";
+
+#[derive(Copy, Clone, ArgEnum)]
+pub enum RdfFormat {
+    /// one triple per line
+    #[clap(name = "ntriples")]
+    NTriples,
+
+    /// one quad per line, in the given graph or the default graph
+    #[clap(name = "nquads")]
+    NQuads,
+
+    /// turtle with prefix declarations, grouped by subject
+    Turtle,
+
+    /// JSON-LD with the prefix declarations as context, one node object per subject
+    #[clap(name = "jsonld")]
+    JsonLd,
+}
+
+impl RdfFormat {
+    pub fn file_extension(self) -> &'static str {
+        match self {
+            RdfFormat::NTriples => "nt",
+            RdfFormat::NQuads => "nq",
+            RdfFormat::Turtle => "ttl",
+            RdfFormat::JsonLd => "jsonld",
+        }
+    }
+
+    /// Whether the format groups triples by subject and therefore needs all triples
+    /// of a subject (and all objects of a subject-predicate pair) to be adjacent
+    pub fn groups_by_subject(self) -> bool {
+        matches!(self, RdfFormat::Turtle | RdfFormat::JsonLd)
+    }
+}
+
+/// Writes the triples (in n-triples term syntax) in the given format.
+///
+/// `prefixes` are used to abbreviate IRIs for turtle and JSON-LD output,
+/// `graph` is the IRI of the named graph for n-quads and JSON-LD output.
+pub fn write_triples<'a, W, I>(
+    writer: &mut W,
+    format: RdfFormat,
+    triples: I,
+    prefixes: &PrefixMap,
+    graph: Option<&str>,
+) -> io::Result<()>
+where
+    W: Write,
+    I: IntoIterator<Item = [&'a str; 3]>,
+{
+    match format {
+        RdfFormat::NTriples => {
+            for [s, p, o] in triples {
+                writeln!(writer, "{s} {p} {o} .")?;
+            }
+
+            Ok(())
+        },
+        RdfFormat::NQuads => {
+            for [s, p, o] in triples {
+                match graph {
+                    Some(graph) => writeln!(writer, "{s} {p} {o} <{graph}> .")?,
+                    None => writeln!(writer, "{s} {p} {o} .")?,
+                }
+            }
+
+            Ok(())
+        },
+        RdfFormat::Turtle => write_turtle(writer, triples, prefixes),
+        RdfFormat::JsonLd => write_json_ld(writer, triples, prefixes, graph),
+    }
+}
+
+/// Parses a term of the compressor state, which are all in n-triples syntax
+pub fn parse_term(term: &str) -> NTriplesTerm<'_> {
+    NTriplesTerm::parse(term).expect("compressor state to contain terms in n-triples syntax")
+}
+
+fn write_turtle<'a, W, I>(writer: &mut W, triples: I, prefixes: &PrefixMap) -> io::Result<()>
+where
+    W: Write,
+    I: IntoIterator<Item = [&'a str; 3]>,
+{
+    for decl in prefixes.iter() {
+        writeln!(writer, "@prefix {}: <{}> .", decl.prefix, decl.namespace)?;
+    }
+
+    if !prefixes.is_empty() {
+        writeln!(writer)?;
+    }
+
+    let mut prev: Option<[&str; 3]> = None;
+
+    for triple @ [s, p, o] in triples {
+        match prev {
+            Some([prev_s, prev_p, _]) if prev_s == s && prev_p == p => {
+                write!(writer, " ,\n        ")?;
+            },
+            Some([prev_s, _, _]) if prev_s == s => {
+                write!(writer, " ;\n    ")?;
+                write_turtle_predicate(writer, p, prefixes)?;
+                write!(writer, " ")?;
+            },
+            _ => {
+                if prev.is_some() {
+                    write!(writer, " .\n\n")?;
+                }
+
+                write_turtle_term(writer, &parse_term(s), prefixes)?;
+                write!(writer, " ")?;
+                write_turtle_predicate(writer, p, prefixes)?;
+                write!(writer, " ")?;
+            },
+        }
+
+        write_turtle_term(writer, &parse_term(o), prefixes)?;
+        prev = Some(triple);
+    }
+
+    if prev.is_some() {
+        writeln!(writer, " .")?;
+    }
+
+    Ok(())
+}
+
+/// Like [`write_turtle_term`] but writes `rdf:type` as `a`
+pub fn write_turtle_predicate<W: Write>(writer: &mut W, predicate: &str, prefixes: &PrefixMap) -> io::Result<()> {
+    match parse_term(predicate) {
+        NTriplesTerm::Iri(RDF_TYPE) => write!(writer, "a"),
+        predicate => write_turtle_term(writer, &predicate, prefixes),
+    }
+}
+
+fn write_turtle_iri<W: Write>(writer: &mut W, iri: &str, prefixes: &PrefixMap) -> io::Result<()> {
+    match prefixes.compact(iri) {
+        Some((prefix, local)) => write!(writer, "{prefix}:{local}"),
+        None => write!(writer, "<{iri}>"),
+    }
+}
+
+/// Writes a term in turtle syntax, which is also valid SPARQL syntax, abbreviating IRIs with `prefixes`
+pub fn write_turtle_term<W: Write>(writer: &mut W, term: &NTriplesTerm, prefixes: &PrefixMap) -> io::Result<()> {
+    match term {
+        NTriplesTerm::Iri(iri) => write_turtle_iri(writer, iri, prefixes),
+        NTriplesTerm::BlankNode(label) => write!(writer, "_:{label}"),
+        NTriplesTerm::Literal { lexical_form, language: Some(language), .. } => {
+            write!(writer, "\"{lexical_form}\"@{language}")
+        },
+        NTriplesTerm::Literal { lexical_form, datatype: Some(datatype), .. } => {
+            write!(writer, "\"{lexical_form}\"^^")?;
+            write_turtle_iri(writer, datatype, prefixes)
+        },
+        NTriplesTerm::Literal { lexical_form, .. } => write!(writer, "\"{lexical_form}\""),
+        NTriplesTerm::QuotedTriple(elements) => {
+            let [s, p, o] = elements.as_ref();
+
+            write!(writer, "<< ")?;
+            write_turtle_term(writer, s, prefixes)?;
+            write!(writer, " ")?;
+            write_turtle_term(writer, p, prefixes)?;
+            write!(writer, " ")?;
+            write_turtle_term(writer, o, prefixes)?;
+            write!(writer, " >>")
+        },
+    }
+}
+
+fn json_ld_iri(iri: &str, prefixes: &PrefixMap) -> String {
+    match prefixes.compact(iri) {
+        Some((prefix, local)) => format!("{prefix}:{local}"),
+        None => iri.to_owned(),
+    }
+}
+
+fn json_ld_predicate(predicate: &str, prefixes: &PrefixMap) -> String {
+    match parse_term(predicate) {
+        NTriplesTerm::Iri(iri) => json_ld_iri(iri, prefixes),
+        _ => predicate.to_owned(),
+    }
+}
+
+/// The value of an `@id` key, quoted triples are represented as embedded nodes (JSON-LD-star)
+fn json_ld_id(term: &NTriplesTerm, prefixes: &PrefixMap) -> Value {
+    match term {
+        NTriplesTerm::Iri(iri) => Value::String(json_ld_iri(iri, prefixes)),
+        NTriplesTerm::BlankNode(label) => Value::String(format!("_:{label}")),
+        NTriplesTerm::QuotedTriple(elements) => json_ld_embedded_node(elements, prefixes),
+        NTriplesTerm::Literal { lexical_form, .. } => Value::String(unescape(lexical_form)),
+    }
+}
+
+fn json_ld_embedded_node(elements: &[NTriplesTerm; 3], prefixes: &PrefixMap) -> Value {
+    let [s, p, o] = elements;
+
+    let predicate = match p {
+        NTriplesTerm::Iri(iri) => json_ld_iri(iri, prefixes),
+        _ => unreachable!("predicates are always IRIs"),
+    };
+
+    let mut node = Map::new();
+    node.insert("@id".to_owned(), json_ld_id(s, prefixes));
+    node.insert(predicate, json_ld_object(o, prefixes));
+
+    Value::Object(node)
+}
+
+fn json_ld_object(term: &NTriplesTerm, prefixes: &PrefixMap) -> Value {
+    let mut object = Map::new();
+
+    match term {
+        NTriplesTerm::Literal { lexical_form, language, datatype } => {
+            object.insert("@value".to_owned(), Value::String(unescape(lexical_form)));
+
+            if let Some(language) = language {
+                object.insert("@language".to_owned(), Value::String((*language).to_owned()));
+            }
+
+            if let Some(datatype) = datatype {
+                object.insert("@type".to_owned(), Value::String(json_ld_iri(datatype, prefixes)));
+            }
+        },
+        term => {
+            object.insert("@id".to_owned(), json_ld_id(term, prefixes));
+        },
+    }
+
+    Value::Object(object)
+}
+
+fn write_json_ld<'a, W, I>(writer: &mut W, triples: I, prefixes: &PrefixMap, graph: Option<&str>) -> io::Result<()>
+where
+    W: Write,
+    I: IntoIterator<Item = [&'a str; 3]>,
+{
+    let context: Map<String, Value> = prefixes
+        .iter()
+        .map(|decl| (decl.prefix.clone(), Value::String(decl.namespace.clone())))
+        .collect();
+
+    writeln!(writer, "{{")?;
+    writeln!(writer, "  \"@context\": {},", Value::Object(context))?;
+
+    if let Some(graph) = graph {
+        writeln!(writer, "  \"@id\": {},", Value::String(graph.to_owned()))?;
+    }
+
+    write!(writer, "  \"@graph\": [")?;
+
+    let mut triples = triples.into_iter().peekable();
+    let mut first = true;
+
+    while let Some([s, p, o]) = triples.next() {
+        let mut node = Map::new();
+        node.insert("@id".to_owned(), json_ld_id(&parse_term(s), prefixes));
+
+        let mut add_object = |p: &str, o: &str| {
+            let objects = node
+                .entry(json_ld_predicate(p, prefixes))
+                .or_insert_with(|| Value::Array(Vec::new()));
+
+            if let Value::Array(objects) = objects {
+                objects.push(json_ld_object(&parse_term(o), prefixes));
+            }
+        };
+
+        add_object(p, o);
+
+        while let Some(&[_, p, o]) = triples.peek().filter(|[next_s, _, _]| *next_s == s) {
+            add_object(p, o);
+            triples.next();
+        }
+
+        if !first {
+            write!(writer, ",")?;
+        }
+
+        write!(writer, "\n    {}", Value::Object(node))?;
+        first = false;
+    }
+
+    writeln!(writer, "\n  ]")?;
+    writeln!(writer, "}}")
+}
@@ -1,2 +1,6 @@
+pub mod canonicalize;
+pub mod format;
+pub mod prefix;
+pub mod term;
 pub mod triple_compressor;
 pub mod triple_generator;
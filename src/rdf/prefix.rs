@@ -0,0 +1,284 @@
+//! Prefix maps for abbreviating IRIs in serialized output
+
+use crate::rdf::term::NTriplesTerm;
+use serde::Deserialize;
+use std::{
+    collections::{BTreeSet, HashMap},
+    str::FromStr,
+};
+
+/// Commonly used prefixes, preferred over generated prefix names when learning namespaces
+const WELL_KNOWN_PREFIXES: &[(&str, &str)] = &[
+    ("rdf", "http://www.w3.org/1999/02/22-rdf-syntax-ns#"),
+    ("rdfs", "http://www.w3.org/2000/01/rdf-schema#"),
+    ("xsd", "http://www.w3.org/2001/XMLSchema#"),
+    ("owl", "http://www.w3.org/2002/07/owl#"),
+    ("skos", "http://www.w3.org/2004/02/skos/core#"),
+    ("foaf", "http://xmlns.com/foaf/0.1/"),
+    ("dc", "http://purl.org/dc/elements/1.1/"),
+    ("dcterms", "http://purl.org/dc/terms/"),
+    ("prov", "http://www.w3.org/ns/prov#"),
+    ("geo", "http://www.w3.org/2003/01/geo/wgs84_pos#"),
+    ("schema", "http://schema.org/"),
+    ("dbo", "http://dbpedia.org/ontology/"),
+    ("dbp", "http://dbpedia.org/property/"),
+    ("dbr", "http://dbpedia.org/resource/"),
+    ("wd", "http://www.wikidata.org/entity/"),
+    ("wdt", "http://www.wikidata.org/prop/direct/"),
+];
+
+/// A single prefix declaration of the form `<PREFIX>=<NAMESPACE_IRI>`
+#[derive(Clone, Debug, Deserialize)]
+#[serde(try_from = "String")]
+pub struct PrefixDecl {
+    pub prefix: String,
+    pub namespace: String,
+}
+
+impl FromStr for PrefixDecl {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (prefix, namespace) = s
+            .split_once('=')
+            .ok_or_else(|| "invalid prefix declaration, expected delimiter '='".to_owned())?;
+
+        if !is_valid_prefix(prefix) {
+            return Err(format!("invalid prefix declaration, {prefix:?} is not a valid prefix name"));
+        }
+
+        let namespace = namespace.trim_start_matches('<').trim_end_matches('>');
+        if namespace.is_empty() {
+            return Err("invalid prefix declaration, namespace is empty".to_owned());
+        }
+
+        Ok(PrefixDecl { prefix: prefix.to_owned(), namespace: namespace.to_owned() })
+    }
+}
+
+impl TryFrom<String> for PrefixDecl {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+/// Maps prefix names to namespace IRIs. IRIs are abbreviated using the longest matching namespace.
+#[derive(Clone, Debug, Default)]
+pub struct PrefixMap {
+    prefixes: Vec<PrefixDecl>,
+}
+
+impl PrefixMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Builds a prefix map from the given declarations, ignoring duplicates with a warning, and additionally
+    /// declares prefixes for the `n_learned` most frequent IRI namespaces of `terms` (see [`Self::learn`])
+    pub fn from_declarations<'a, I>(decls: Vec<PrefixDecl>, terms: I, n_learned: usize) -> Self
+    where
+        I: IntoIterator<Item = &'a str>,
+    {
+        let mut prefix_map = Self::new();
+
+        for decl in decls {
+            if !prefix_map.insert(decl.clone()) {
+                eprintln!("Warning: ignoring duplicate prefix declaration {}={}", decl.prefix, decl.namespace);
+            }
+        }
+
+        if n_learned > 0 {
+            println!("learning prefixes from compressor state...");
+            prefix_map.learn(terms, n_learned);
+        }
+
+        prefix_map
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.prefixes.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &PrefixDecl> {
+        self.prefixes.iter()
+    }
+
+    /// Adds a prefix declaration, returns false if the prefix name or namespace is already declared
+    pub fn insert(&mut self, decl: PrefixDecl) -> bool {
+        if self
+            .prefixes
+            .iter()
+            .any(|PrefixDecl { prefix, namespace }| *prefix == decl.prefix || *namespace == decl.namespace)
+        {
+            return false;
+        }
+
+        self.prefixes.push(decl);
+        true
+    }
+
+    /// Declares prefixes for the `n` most frequent IRI namespaces of the given terms
+    /// that are not yet covered by this map.
+    /// Terms are expected to be in n-triples syntax, e.g. as stored in the compressor state.
+    pub fn learn<'a, I>(&mut self, terms: I, n: usize)
+    where
+        I: IntoIterator<Item = &'a str>,
+    {
+        let mut namespace_counts: HashMap<&str, usize> = HashMap::new();
+
+        for term in terms {
+            if let Some(term) = NTriplesTerm::parse(term) {
+                count_namespaces(&term, &mut namespace_counts);
+            }
+        }
+
+        let mut namespaces: Vec<_> = namespace_counts
+            .into_iter()
+            .filter(|(namespace, _)| !self.prefixes.iter().any(|decl| decl.namespace == *namespace))
+            .collect();
+
+        // break ties by namespace to get the same prefixes between program executions
+        namespaces.sort_unstable_by(|(ns1, n1), (ns2, n2)| n2.cmp(n1).then_with(|| ns1.cmp(ns2)));
+
+        for (namespace, _) in namespaces.into_iter().take(n) {
+            let prefix = self.generate_prefix_name(namespace);
+            self.insert(PrefixDecl { prefix, namespace: namespace.to_owned() });
+        }
+    }
+
+    fn generate_prefix_name(&self, namespace: &str) -> String {
+        let is_free = |prefix: &str| !self.prefixes.iter().any(|decl| decl.prefix == prefix);
+
+        if let Some((prefix, _)) = WELL_KNOWN_PREFIXES
+            .iter()
+            .find(|(prefix, ns)| *ns == namespace && is_free(prefix))
+        {
+            return (*prefix).to_owned();
+        }
+
+        // use the last path segment or the first host label, e.g. "ontology" for "http://dbpedia.org/ontology/"
+        // or "example" for "http://www.example.org/"
+        let without_scheme = namespace.split_once("://").map_or(namespace, |(_, rest)| rest);
+        let trimmed = without_scheme.trim_end_matches(['/', '#']);
+
+        let candidate = match trimmed.rsplit_once(['/', '#']) {
+            Some((_, last_segment)) => last_segment,
+            None => trimmed.split('.').find(|label| *label != "www").unwrap_or_default(),
+        };
+
+        let candidate: String = candidate
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric())
+            .map(|c| c.to_ascii_lowercase())
+            .collect();
+
+        let candidate = if candidate.starts_with(|c: char| c.is_ascii_alphabetic()) {
+            candidate
+        } else {
+            "ns".to_owned()
+        };
+
+        if is_free(&candidate) {
+            return candidate;
+        }
+
+        (1..)
+            .map(|i| format!("{candidate}{i}"))
+            .find(|prefix| is_free(prefix))
+            .expect("unbounded range to yield a free prefix name")
+    }
+
+    /// Splits `iri` into a declared prefix and a local name, if that results in a valid prefixed name
+    pub fn compact<'a>(&'a self, iri: &'a str) -> Option<(&'a str, &'a str)> {
+        self.prefixes
+            .iter()
+            .filter_map(|PrefixDecl { prefix, namespace }| {
+                let local = iri.strip_prefix(namespace.as_str())?;
+                Some((prefix.as_str(), namespace.len(), local))
+            })
+            .filter(|(_, _, local)| is_valid_local_name(local))
+            .max_by_key(|(_, namespace_len, _)| *namespace_len)
+            .map(|(prefix, _, local)| (prefix, local))
+    }
+
+    /// Collects the prefix names that [`Self::compact`] uses for the IRIs in `term`
+    pub fn collect_used<'a>(&'a self, term: &NTriplesTerm, used: &mut BTreeSet<&'a str>) {
+        match term {
+            NTriplesTerm::Iri(iri) | NTriplesTerm::Literal { datatype: Some(iri), .. } => {
+                // look the prefix up again so that it borrows from the map rather than the term
+                let decl = self
+                    .compact(iri)
+                    .and_then(|(prefix, _)| self.prefixes.iter().find(|decl| decl.prefix == prefix));
+
+                used.extend(decl.map(|decl| decl.prefix.as_str()));
+            },
+            NTriplesTerm::QuotedTriple(elements) => {
+                for element in elements.iter() {
+                    self.collect_used(element, used);
+                }
+            },
+            NTriplesTerm::Literal { .. } | NTriplesTerm::BlankNode(_) => (),
+        }
+    }
+
+    /// The namespace IRI of a declared prefix name
+    pub fn namespace(&self, prefix: &str) -> Option<&str> {
+        self.prefixes.iter().find(|decl| decl.prefix == prefix).map(|decl| decl.namespace.as_str())
+    }
+}
+
+fn count_namespaces<'a>(term: &NTriplesTerm<'a>, counts: &mut HashMap<&'a str, usize>) {
+    match term {
+        NTriplesTerm::Iri(iri) => {
+            if let Some(namespace) = namespace_of(iri) {
+                *counts.entry(namespace).or_default() += 1;
+            }
+        },
+        NTriplesTerm::Literal { datatype: Some(datatype), .. } => {
+            if let Some(namespace) = namespace_of(datatype) {
+                *counts.entry(namespace).or_default() += 1;
+            }
+        },
+        NTriplesTerm::QuotedTriple(elements) => {
+            for element in elements.iter() {
+                count_namespaces(element, counts);
+            }
+        },
+        NTriplesTerm::Literal { .. } | NTriplesTerm::BlankNode(_) => (),
+    }
+}
+
+/// The namespace of an IRI is everything up to and including the last `#` or `/`,
+/// provided that the remaining local name is a valid local name
+fn namespace_of(iri: &str) -> Option<&str> {
+    let split = iri.rfind(['#', '/'])? + 1;
+    let (namespace, local) = iri.split_at(split);
+
+    if namespace.ends_with("//") || !is_valid_local_name(local) {
+        return None;
+    }
+
+    Some(namespace)
+}
+
+/// Conservative subset of the turtle and SPARQL `PN_PREFIX` production
+pub fn is_valid_prefix(prefix: &str) -> bool {
+    prefix.is_empty()
+        || (prefix.starts_with(|c: char| c.is_ascii_alphabetic())
+            && !prefix.ends_with('.')
+            && prefix
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.')))
+}
+
+/// Conservative subset of the turtle and SPARQL `PN_LOCAL` production, only allowing characters that never need escaping
+pub fn is_valid_local_name(local: &str) -> bool {
+    local.is_empty()
+        || (local.starts_with(|c: char| c.is_ascii_alphanumeric() || c == '_')
+            && !local.ends_with('.')
+            && local
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.')))
+}
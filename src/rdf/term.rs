@@ -0,0 +1,118 @@
+//! Parsing of the n-triples term syntax in which terms are stored in the compressor state
+
+/// A borrowed view into the n-triples representation of a single term
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum NTriplesTerm<'a> {
+    /// The IRI without the enclosing `<` and `>`
+    Iri(&'a str),
+    /// The blank node label without the leading `_:`
+    BlankNode(&'a str),
+    Literal {
+        /// The still escaped lexical form without the enclosing quotes
+        lexical_form: &'a str,
+        language: Option<&'a str>,
+        /// The datatype IRI without the enclosing `<` and `>`
+        datatype: Option<&'a str>,
+    },
+    QuotedTriple(Box<[NTriplesTerm<'a>; 3]>),
+}
+
+impl<'a> NTriplesTerm<'a> {
+    /// Parses a single term, the whole input must be consumed
+    pub fn parse(s: &'a str) -> Option<Self> {
+        let (term, rest) = Self::parse_prefix(s.trim_start())?;
+
+        if rest.trim().is_empty() {
+            Some(term)
+        } else {
+            None
+        }
+    }
+
+    fn parse_prefix(s: &'a str) -> Option<(Self, &'a str)> {
+        if let Some(rest) = s.strip_prefix("<<") {
+            let (subject, rest) = Self::parse_prefix(rest.trim_start())?;
+            let (predicate, rest) = Self::parse_prefix(rest.trim_start())?;
+            let (object, rest) = Self::parse_prefix(rest.trim_start())?;
+            let rest = rest.trim_start().strip_prefix(">>")?;
+
+            Some((NTriplesTerm::QuotedTriple(Box::new([subject, predicate, object])), rest))
+        } else if let Some(rest) = s.strip_prefix('<') {
+            let (iri, rest) = rest.split_once('>')?;
+            Some((NTriplesTerm::Iri(iri), rest))
+        } else if let Some(rest) = s.strip_prefix("_:") {
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            let (label, rest) = rest.split_at(end);
+            Some((NTriplesTerm::BlankNode(label), rest))
+        } else if let Some(rest) = s.strip_prefix('"') {
+            let end = find_closing_quote(rest)?;
+            let (lexical_form, rest) = (&rest[..end], &rest[end + 1..]);
+
+            if let Some(rest) = rest.strip_prefix('@') {
+                let end = rest
+                    .find(|c: char| !(c.is_ascii_alphanumeric() || c == '-'))
+                    .unwrap_or(rest.len());
+                let (language, rest) = rest.split_at(end);
+
+                Some((NTriplesTerm::Literal { lexical_form, language: Some(language), datatype: None }, rest))
+            } else if let Some(rest) = rest.strip_prefix("^^<") {
+                let (datatype, rest) = rest.split_once('>')?;
+
+                Some((NTriplesTerm::Literal { lexical_form, language: None, datatype: Some(datatype) }, rest))
+            } else {
+                Some((NTriplesTerm::Literal { lexical_form, language: None, datatype: None }, rest))
+            }
+        } else {
+            None
+        }
+    }
+}
+
+fn find_closing_quote(s: &str) -> Option<usize> {
+    let mut escaped = false;
+
+    for (ix, b) in s.bytes().enumerate() {
+        match b {
+            _ if escaped => escaped = false,
+            b'\\' => escaped = true,
+            b'"' => return Some(ix),
+            _ => (),
+        }
+    }
+
+    None
+}
+
+/// Resolves the escape sequences (`ECHAR` and `UCHAR`) of an n-triples string
+pub fn unescape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('t') => out.push('\t'),
+            Some('b') => out.push('\u{8}'),
+            Some('n') => out.push('\n'),
+            Some('r') => out.push('\r'),
+            Some('f') => out.push('\u{c}'),
+            Some(c @ ('u' | 'U')) => {
+                let n_digits = if c == 'u' { 4 } else { 8 };
+                let digits: String = chars.by_ref().take(n_digits).collect();
+
+                match u32::from_str_radix(&digits, 16).ok().and_then(char::from_u32) {
+                    Some(c) => out.push(c),
+                    None => out.push(char::REPLACEMENT_CHARACTER),
+                }
+            },
+            Some(c) => out.push(c),
+            None => out.push('\\'),
+        }
+    }
+
+    out
+}
@@ -1,11 +1,15 @@
 use super::TripleElementId;
-use crate::rdf::triple_compressor::TripleId;
+use crate::rdf::{
+    canonicalize::{canonicalize_iri, canonicalize_language_tag, canonicalize_lexical_form},
+    triple_compressor::TripleId,
+};
 use rio_api::{
-    model::{Subject, Term, Triple},
+    model::{Literal, NamedNode, Subject, Term, Triple},
     parser::TriplesParser,
 };
 use std::{
     collections::{BTreeMap, HashSet},
+    fmt::Display,
     fs::{File, OpenOptions},
     hash::{BuildHasher, BuildHasherDefault, Hash, Hasher},
     io::{BufReader, BufWriter, Write},
@@ -18,6 +22,35 @@ fn hash_single<T: Hash, H: BuildHasher>(to_hash: T, build_hasher: H) -> u64 {
     hasher.finish()
 }
 
+/// Distinguishes the hash of a quoted triple from the hash of the triple's element ids
+const QUOTED_TRIPLE_HASH_TAG: &str = "<< >>";
+
+/// Checks whether the triple can be used in a SPARQL DELETE DATA query, i.e. it does not contain any blank nodes,
+/// not even inside of quoted triples
+fn is_ground_triple(triple: &Triple) -> bool {
+    let ground_subject = match triple.subject {
+        Subject::NamedNode(_) => true,
+        Subject::Triple(quoted) => is_ground_triple(quoted),
+        Subject::BlankNode(_) => false,
+    };
+
+    let ground_object = match triple.object {
+        Term::NamedNode(_) | Term::Literal(_) => true,
+        Term::Triple(quoted) => is_ground_triple(quoted),
+        Term::BlankNode(_) => false,
+    };
+
+    ground_subject && ground_object
+}
+
+/// Position of a term in a triple, the id of an IRI depends on whether it is used as a predicate
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TermPosition {
+    /// subjects and objects share their ids, e.g. an IRI has the same id as a subject and as an object
+    SubjectOrObject,
+    Predicate,
+}
+
 #[derive(Default)]
 pub struct RdfTripleCompressor {
     translations: BTreeMap<TripleElementId, String>,
@@ -39,7 +72,7 @@ impl RdfTripleCompressor {
     pub fn save_state<P: AsRef<Path>>(&mut self, path: P) -> std::io::Result<()> {
         let header_size = self.translations.len() * std::mem::size_of::<(TripleElementId, usize, usize)>();
 
-        let f = OpenOptions::new().write(true).create(true).open(&path)?;
+        let f = OpenOptions::new().write(true).create(true).truncate(true).open(&path)?;
         let mut bw = BufWriter::new(f);
 
         bw.write_all(&header_size.to_ne_bytes())?;
@@ -72,27 +105,129 @@ impl RdfTripleCompressor {
         Self { translations, dedup: HashSet::default() }
     }
 
+    pub fn from_translations<I>(translations: I) -> Self
+    where
+        I: IntoIterator<Item = (TripleElementId, String)>,
+    {
+        Self { translations: translations.into_iter().collect(), dedup: HashSet::default() }
+    }
+
     pub fn compress_rdf_triple(&mut self, triple: Triple) -> [TripleElementId; 3] {
-        type BuildHasher = BuildHasherDefault<ahash::AHasher>;
-
-        let subject_hash = hash_single(triple.subject, BuildHasher::default());
-        let predicate_hash = hash_single(triple.predicate, BuildHasher::default());
-        let object_hash = hash_single(triple.object, BuildHasher::default());
-
-        self.translations
-            .entry(subject_hash)
-            .or_insert_with(|| triple.subject.to_string());
-        self.translations
-            .entry(predicate_hash)
-            .or_insert_with(|| triple.predicate.to_string());
-        self.translations
-            .entry(object_hash)
-            .or_insert_with(|| triple.object.to_string());
+        self.compress_rdf_triple_impl(triple, false)
+    }
+
+    /// Like [`Self::compress_rdf_triple`] but canonicalizes IRIs, language tags and
+    /// literal lexical forms before interning them.
+    /// See [`crate::rdf::canonicalize`] for the supported canonicalizations.
+    pub fn compress_canonical_rdf_triple(&mut self, triple: Triple) -> [TripleElementId; 3] {
+        self.compress_rdf_triple_impl(triple, true)
+    }
+
+    fn compress_rdf_triple_impl(&mut self, triple: Triple, canonicalize: bool) -> [TripleElementId; 3] {
+        let subject_hash = match triple.subject {
+            Subject::Triple(quoted) => self.intern_quoted_triple(quoted, canonicalize),
+            Subject::NamedNode(NamedNode { iri }) if canonicalize => {
+                self.intern(Subject::NamedNode(NamedNode { iri: &canonicalize_iri(iri) }))
+            },
+            subject => self.intern(subject),
+        };
+
+        let predicate_hash = if canonicalize {
+            self.intern(NamedNode { iri: &canonicalize_iri(triple.predicate.iri) })
+        } else {
+            self.intern(triple.predicate)
+        };
+
+        let object_hash = match triple.object {
+            Term::Triple(quoted) => self.intern_quoted_triple(quoted, canonicalize),
+            Term::NamedNode(NamedNode { iri }) if canonicalize => {
+                self.intern(Term::NamedNode(NamedNode { iri: &canonicalize_iri(iri) }))
+            },
+            Term::Literal(Literal::LanguageTaggedString { value, language }) if canonicalize => {
+                self.intern(Term::Literal(Literal::LanguageTaggedString {
+                    value,
+                    language: &canonicalize_language_tag(language),
+                }))
+            },
+            Term::Literal(Literal::Typed { value, datatype }) if canonicalize => {
+                let datatype = canonicalize_iri(datatype.iri);
+                let value = canonicalize_lexical_form(value, &datatype);
+
+                self.intern(Term::Literal(Literal::Typed { value: &value, datatype: NamedNode { iri: &datatype } }))
+            },
+            object => self.intern(object),
+        };
 
         [subject_hash, predicate_hash, object_hash]
     }
 
-    pub fn compress_rdf_triple_file<P: AsRef<Path>>(&mut self, path: P, dedup: bool) -> std::io::Result<()> {
+    fn intern<T: Hash + Display>(&mut self, term: T) -> TripleElementId {
+        let hash = hash_single(&term, BuildHasherDefault::<ahash::AHasher>::default());
+        self.translations.entry(hash).or_insert_with(|| term.to_string());
+        hash
+    }
+
+    /// Interns an RDF-star quoted triple as a single term.
+    /// Its id is derived from the ids of its elements and its translation is the SPARQL-star/N-Triples-star
+    /// syntax `<< s p o >>`, so that it can be written to queries and n-triple files verbatim.
+    fn intern_quoted_triple(&mut self, quoted: &Triple, canonicalize: bool) -> TripleElementId {
+        let elements = self.compress_rdf_triple_impl(*quoted, canonicalize);
+        let hash = hash_single(
+            (QUOTED_TRIPLE_HASH_TAG, elements),
+            BuildHasherDefault::<ahash::AHasher>::default(),
+        );
+
+        if !self.translations.contains_key(&hash) {
+            let [s, p, o] = elements.map(|element| &self.translations[&element]);
+            let translation = format!("<< {s} {p} {o} >>");
+
+            self.translations.insert(hash, translation);
+        }
+
+        hash
+    }
+
+    /// Computes the ids that the triples of the given n-triples document have (or would have)
+    /// in a compressor state, without modifying any compressor state
+    pub fn rdf_triple_ids(ntriples: &str, canonicalize: bool) -> std::io::Result<Vec<[TripleElementId; 3]>> {
+        let mut scratch = Self::new();
+        let mut ids = Vec::new();
+
+        rio_turtle::NTriplesParser::new(ntriples.as_bytes()).parse_all(&mut |triple| -> std::io::Result<()> {
+            ids.push(scratch.compress_rdf_triple_impl(triple, canonicalize));
+            Ok(())
+        })?;
+
+        Ok(ids)
+    }
+
+    /// Computes the id that the given term (in n-triples syntax) has (or would have) in a compressor state
+    /// at the given position.
+    /// Predicates are interned as bare IRIs while subjects and objects are interned together with their kind,
+    /// so an IRI has a different id as a predicate than as a subject or object.
+    pub fn rdf_term_id(term: &str, position: TermPosition, canonicalize: bool) -> std::io::Result<TripleElementId> {
+        let dummy_triple = match position {
+            TermPosition::SubjectOrObject => format!("<urn:lookup> <urn:lookup> {term} ."),
+            TermPosition::Predicate => format!("<urn:lookup> {term} <urn:lookup> ."),
+        };
+
+        let ids = Self::rdf_triple_ids(&dummy_triple, canonicalize)?;
+
+        match (ids.as_slice(), position) {
+            ([[_, _, id]], TermPosition::SubjectOrObject) | ([[_, id, _]], TermPosition::Predicate) => Ok(*id),
+            _ => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("expected exactly one term, got {term:?}"),
+            )),
+        }
+    }
+
+    pub fn compress_rdf_triple_file<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        dedup: bool,
+        canonicalize: bool,
+    ) -> std::io::Result<()> {
         let out_path = path.as_ref().with_extension(super::COMPRESSED_TRIPLE_FILE_EXTENSION);
 
         let mut bw = BufWriter::new(File::options().write(true).create_new(true).open(&out_path)?);
@@ -114,18 +249,16 @@ impl RdfTripleCompressor {
             let reader = s.spawn(move || -> std::io::Result<()> {
                 while !triples.is_end() {
                     let res: Result<(), std::io::Error> = triples.parse_step(&mut |triple| {
-                        let subject@Subject::NamedNode(_) = triple.subject else {
+                        if !is_ground_triple(&triple) {
                             return Ok(());
-                        };
-
-                        let predicate = triple.predicate;
+                        }
 
-                        let object@(Term::NamedNode(_) | Term::Literal(_)) = triple.object else {
-                            return Ok(());
+                        let triple = if canonicalize {
+                            self.compress_canonical_rdf_triple(triple)
+                        } else {
+                            self.compress_rdf_triple(triple)
                         };
 
-                        let triple = self.compress_rdf_triple(Triple { subject, predicate, object });
-
                         if !dedup || self.found_new_triple(triple) {
                             tx.send(triple).unwrap();
                         }
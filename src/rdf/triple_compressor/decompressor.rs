@@ -1,9 +1,13 @@
-use super::{CompressedRdfTriples, UNCOMPRESSED_TRIPLE_FILE_EXTENSION};
-use crate::rdf::triple_compressor::TripleElementId;
+use super::CompressedRdfTriples;
+use crate::rdf::{
+    format::{write_triples, RdfFormat},
+    prefix::PrefixMap,
+    triple_compressor::TripleElementId,
+};
 use memory_mapped::MemoryMapped;
 use std::{
     fs::File,
-    io::{BufWriter, Read, Write},
+    io::{BufWriter, Read},
     path::Path,
 };
 
@@ -44,37 +48,63 @@ impl RdfTripleDecompressor {
         Ok(Self { header, data_segment })
     }
 
+    /// Looks up the term (in n-triples syntax) with the given id
+    pub fn decompress_term(&self, id: TripleElementId) -> Option<&str> {
+        let &(_, start, end) = self.search_header(id)?;
+        Some(unsafe { std::str::from_utf8_unchecked(&self.data_segment[start..end]) })
+    }
+
     pub fn decompress_rdf_triple(&self, [subject, predicate, object]: &[TripleElementId; 3]) -> Option<[&str; 3]> {
-        let &(_, s_start, s_end) = self.search_header(*subject)?;
-        let &(_, p_start, p_end) = self.search_header(*predicate)?;
-        let &(_, o_start, o_end) = self.search_header(*object)?;
-
-        Some(unsafe {
-            [
-                std::str::from_utf8_unchecked(&self.data_segment[s_start..s_end]),
-                std::str::from_utf8_unchecked(&self.data_segment[p_start..p_end]),
-                std::str::from_utf8_unchecked(&self.data_segment[o_start..o_end]),
-            ]
+        Some([
+            self.decompress_term(*subject)?,
+            self.decompress_term(*predicate)?,
+            self.decompress_term(*object)?,
+        ])
+    }
+
+    /// Iterates over all terms in the compressor state and their ids, ordered by id
+    pub fn terms(&self) -> impl Iterator<Item = (TripleElementId, &str)> + '_ {
+        self.header.iter().map(|&(hash, s_start, s_end)| {
+            (hash, unsafe { std::str::from_utf8_unchecked(&self.data_segment[s_start..s_end]) })
         })
     }
 
-    pub fn decompress_rdf_triple_file<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
-        let out_path = path.as_ref().with_extension(UNCOMPRESSED_TRIPLE_FILE_EXTENSION);
+    /// Decompresses the dataset at `path` into a file next to it with the extension of the given format.
+    ///
+    /// For formats that group triples by subject the triples are sorted first if the dataset is not sorted already.
+    pub fn decompress_rdf_triple_file<P: AsRef<Path>>(
+        &self,
+        path: P,
+        format: RdfFormat,
+        prefixes: &PrefixMap,
+        graph: Option<&str>,
+    ) -> std::io::Result<()> {
+        let out_path = path.as_ref().with_extension(format.file_extension());
 
         let in_triples = unsafe { CompressedRdfTriples::load(path)? };
 
+        let sorted;
+        let triples: &[[TripleElementId; 3]] = if format.groups_by_subject() && !in_triples.is_sorted() {
+            sorted = {
+                let mut tmp = in_triples.to_vec();
+                tmp.sort_unstable();
+                tmp
+            };
+
+            &sorted
+        } else {
+            &in_triples
+        };
+
         let out_f = File::options().write(true).create_new(true).open(out_path)?;
 
         let mut bw = BufWriter::new(out_f);
 
-        for triple in in_triples.iter() {
-            let [s, p, o] = self
-                .decompress_rdf_triple(triple)
-                .expect("using same compressor state for compression and decompression");
-
-            writeln!(bw, "{s} {p} {o} .")?;
-        }
+        let decompressed = triples.iter().map(|triple| {
+            self.decompress_rdf_triple(triple)
+                .expect("using same compressor state for compression and decompression")
+        });
 
-        Ok(())
+        write_triples(&mut bw, format, decompressed, prefixes, graph)
     }
 }
@@ -0,0 +1,273 @@
+//! Export and import of the term dictionary of a compressor state as TSV or CSV
+
+use super::{
+    compressor::{RdfTripleCompressor, TermPosition},
+    decompressor::RdfTripleDecompressor,
+    TripleElementId,
+};
+use crate::rdf::term::NTriplesTerm;
+use clap::ArgEnum;
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    io::{self, BufRead, Write},
+};
+
+#[derive(Copy, Clone, ArgEnum)]
+pub enum DictionaryFormat {
+    /// tab separated `id<TAB>term` lines, backslashes, tabs and line breaks in terms are escaped as `\\`, `\t`,
+    /// `\n` and `\r`
+    Tsv,
+
+    /// comma separated `id,term` records as described in RFC 4180
+    Csv,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum DictionaryError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+
+    #[error("line {line}: {reason}")]
+    Malformed { line: usize, reason: String },
+
+    #[error("line {line}: id {id} is already assigned to {existing}")]
+    DuplicateId { line: usize, id: TripleElementId, existing: String },
+
+    #[error("line {line}: term {term} is already assigned to id {existing}")]
+    DuplicateTerm { line: usize, term: String, existing: TripleElementId },
+
+    #[error("line {line}: id {id} of term {term} does not match any of the computed ids {computed:?}")]
+    IdMismatch { line: usize, id: TripleElementId, term: String, computed: Vec<TripleElementId> },
+}
+
+const HEADER: [&str; 2] = ["id", "term"];
+
+fn escape_tsv_field(term: &str) -> String {
+    term.replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
+
+/// Reverses [`escape_tsv_field`], unknown escape sequences are kept as is
+fn unescape_tsv_field(field: &str) -> String {
+    let mut unescaped = String::with_capacity(field.len());
+    let mut chars = field.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('\\') => unescaped.push('\\'),
+            Some('t') => unescaped.push('\t'),
+            Some('n') => unescaped.push('\n'),
+            Some('r') => unescaped.push('\r'),
+            Some(other) => {
+                unescaped.push('\\');
+                unescaped.push(other);
+            },
+            None => unescaped.push('\\'),
+        }
+    }
+
+    unescaped
+}
+
+/// The ids a term can have, an IRI has a different id as predicate than as subject or object
+fn candidate_ids(term: &NTriplesTerm, raw_term: &str) -> io::Result<Vec<TripleElementId>> {
+    let positions: &[_] = match term {
+        NTriplesTerm::Iri(_) => &[TermPosition::SubjectOrObject, TermPosition::Predicate],
+        _ => &[TermPosition::SubjectOrObject],
+    };
+
+    positions
+        .iter()
+        .map(|&position| RdfTripleCompressor::rdf_term_id(raw_term, position, false))
+        .collect()
+}
+
+pub fn export_dictionary<W: Write>(
+    decompressor: &RdfTripleDecompressor,
+    writer: &mut W,
+    format: DictionaryFormat,
+) -> io::Result<()> {
+    match format {
+        DictionaryFormat::Tsv => {
+            writeln!(writer, "{}\t{}", HEADER[0], HEADER[1])?;
+
+            for (id, term) in decompressor.terms() {
+                writeln!(writer, "{id}\t{}", escape_tsv_field(term))?;
+            }
+        },
+        DictionaryFormat::Csv => {
+            writeln!(writer, "{},{}", HEADER[0], HEADER[1])?;
+
+            for (id, term) in decompressor.terms() {
+                if term.contains(['"', ',', '\n', '\r']) {
+                    writeln!(writer, "{id},\"{}\"", term.replace('"', "\"\""))?;
+                } else {
+                    writeln!(writer, "{id},{term}")?;
+                }
+            }
+        },
+    }
+
+    Ok(())
+}
+
+/// Splits a CSV record into its fields, returns `None` if a quoted field is not terminated
+fn split_csv_record(record: &str) -> Option<Vec<String>> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut chars = record.chars().peekable();
+    let mut in_quotes = false;
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            },
+            '"' => in_quotes = !in_quotes,
+            ',' if !in_quotes => fields.push(std::mem::take(&mut field)),
+            c => field.push(c),
+        }
+    }
+
+    if in_quotes {
+        return None;
+    }
+
+    fields.push(field);
+    Some(fields)
+}
+
+/// Builds a compressor from a dictionary previously written by [`export_dictionary`] or another tool.
+///
+/// Every term must be a valid n-triples term and ids must be unique. Terms must be unique too, except for IRIs
+/// which may have two ids, one as subject or object and one as predicate.
+/// If `verify_ids` is set every id must also be one of the ids that this tool computes for the term,
+/// otherwise arbitrary ids are accepted.
+pub fn import_dictionary<R: BufRead>(
+    reader: R,
+    format: DictionaryFormat,
+    verify_ids: bool,
+) -> Result<RdfTripleCompressor, DictionaryError> {
+    let mut translations: HashMap<TripleElementId, String> = HashMap::new();
+    let mut ids_by_term: HashMap<String, Vec<TripleElementId>> = HashMap::new();
+
+    let mut lines = reader.lines().enumerate().map(|(ix, line)| (ix + 1, line));
+
+    while let Some((line_no, line)) = lines.next() {
+        let mut line = line?;
+
+        let (id, term) = match format {
+            DictionaryFormat::Tsv => match line.split_once('\t') {
+                Some((id, term)) => (id.to_owned(), unescape_tsv_field(term)),
+                None => {
+                    return Err(DictionaryError::Malformed {
+                        line: line_no,
+                        reason: "expected tab delimiter".to_owned(),
+                    })
+                },
+            },
+            DictionaryFormat::Csv => {
+                // quoted fields may contain line breaks, so keep reading until the record is complete
+                let fields = loop {
+                    if let Some(fields) = split_csv_record(&line) {
+                        break fields;
+                    }
+
+                    match lines.next() {
+                        Some((_, next_line)) => {
+                            line.push('\n');
+                            line.push_str(&next_line?);
+                        },
+                        None => {
+                            return Err(DictionaryError::Malformed {
+                                line: line_no,
+                                reason: "unterminated quoted field".to_owned(),
+                            })
+                        },
+                    }
+                };
+
+                match <[String; 2]>::try_from(fields) {
+                    Ok([id, term]) => (id, term),
+                    Err(fields) => {
+                        return Err(DictionaryError::Malformed {
+                            line: line_no,
+                            reason: format!("expected 2 fields, got {}", fields.len()),
+                        })
+                    },
+                }
+            },
+        };
+
+        if line_no == 1 && id.trim().eq_ignore_ascii_case(HEADER[0]) {
+            continue;
+        }
+
+        let id: TripleElementId = id.trim().parse().map_err(|e| DictionaryError::Malformed {
+            line: line_no,
+            reason: format!("id is not an unsigned integer: {e:?}"),
+        })?;
+
+        let Some(parsed) = NTriplesTerm::parse(&term) else {
+            return Err(DictionaryError::Malformed {
+                line: line_no,
+                reason: format!("{term} is not a valid n-triples term"),
+            });
+        };
+
+        let max_ids = if let NTriplesTerm::Iri(_) = parsed { 2 } else { 1 };
+
+        if verify_ids {
+            let computed = candidate_ids(&parsed, &term)
+                .map_err(|e| DictionaryError::Malformed { line: line_no, reason: e.to_string() })?;
+
+            if !computed.contains(&id) {
+                return Err(DictionaryError::IdMismatch { line: line_no, id, term, computed });
+            }
+        }
+
+        let ids = ids_by_term.entry(term.clone()).or_default();
+        if !ids.contains(&id) {
+            if ids.len() == max_ids {
+                return Err(DictionaryError::DuplicateTerm { line: line_no, term, existing: ids[0] });
+            }
+
+            ids.push(id);
+        }
+
+        match translations.entry(id) {
+            Entry::Occupied(existing) if *existing.get() != term => {
+                return Err(DictionaryError::DuplicateId { line: line_no, id, existing: existing.get().clone() });
+            },
+            Entry::Occupied(_) => (),
+            Entry::Vacant(v) => {
+                v.insert(term);
+            },
+        }
+    }
+
+    Ok(RdfTripleCompressor::from_translations(translations))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tsv_escaping_round_trips() {
+        for term in ["\"a\tb\"", "\"a\\tb\"", "\"line\nbreak\r\n\"", "\"\\\\\t\"", "<http://example.org/a>"] {
+            assert_eq!(unescape_tsv_field(&escape_tsv_field(term)), term);
+            assert!(!escape_tsv_field(term).contains(['\t', '\n', '\r']));
+        }
+
+        assert_eq!(unescape_tsv_field("\"a\\u0041\""), "\"a\\u0041\"");
+    }
+}
@@ -1,8 +1,13 @@
 pub mod compressor;
 pub mod decompressor;
+pub mod dictionary;
 
 use crate::MemoryMapped;
 use std::{
+    collections::HashSet,
+    fs::File,
+    hash::BuildHasherDefault,
+    io::{BufWriter, Write},
     ops::{Deref, DerefMut},
     path::Path,
 };
@@ -14,6 +19,9 @@ pub const UNCOMPRESSED_TRIPLE_FILE_EXTENSION: &str = "nt";
 pub type TripleId = u64;
 pub type TripleElementId = u64;
 
+/// A set of compressed triples, hashed with ahash without std so that iteration order is stable between runs
+pub type TripleSet = HashSet<[TripleElementId; 3], BuildHasherDefault<ahash::AHasher>>;
+
 pub struct CompressedRdfTriples(MemoryMapped<[[TripleElementId; 3]]>);
 
 impl CompressedRdfTriples {
@@ -34,6 +42,51 @@ impl CompressedRdfTriples {
     pub fn contains(&self, triple: &[TripleElementId; 3]) -> bool {
         self.0.binary_search(triple).is_ok()
     }
+
+    /// Writes all triples that are not in `excluded` to a new file at `path` and loads it.
+    /// The order of the remaining triples is kept, so the result is sorted if `self` is.
+    /// The file is removed again before returning, also if writing fails, the mapping stays valid without it.
+    pub unsafe fn without<P: AsRef<Path>>(&self, excluded: &TripleSet, path: P) -> std::io::Result<Self> {
+        let file = File::options().write(true).create_new(true).open(&path)?;
+        let _remove_file = RemoveOnDrop(path.as_ref());
+        let mut bw = BufWriter::new(file);
+
+        for triple in self.iter().filter(|triple| !excluded.contains(*triple)) {
+            for element in triple {
+                bw.write_all(&element.to_ne_bytes())?;
+            }
+        }
+
+        bw.flush()?;
+        Self::load(&path)
+    }
+
+    /// Maps the given triples from a file named after `name` that is removed right away
+    #[cfg(test)]
+    pub fn from_triples(name: &str, triples: &[[TripleElementId; 3]]) -> Self {
+        let path = std::env::temp_dir().join(format!(
+            "{}-{}-{name}.{COMPRESSED_TRIPLE_FILE_EXTENSION}",
+            env!("CARGO_PKG_NAME"),
+            std::process::id()
+        ));
+
+        let bytes: Vec<u8> = triples.iter().flatten().flat_map(|element| element.to_ne_bytes()).collect();
+        std::fs::write(&path, bytes).unwrap();
+
+        let _remove_file = RemoveOnDrop(&path);
+        unsafe { Self::load(&path) }.unwrap()
+    }
+}
+
+/// Removes the file at the path when dropped
+struct RemoveOnDrop<'p>(&'p Path);
+
+impl Drop for RemoveOnDrop<'_> {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_file(self.0) {
+            eprintln!("Error: unable to remove {:?}: {e:?}", self.0);
+        }
+    }
 }
 
 impl Deref for CompressedRdfTriples {
@@ -1,29 +1,384 @@
-use crate::rdf::triple_compressor::{CompressedRdfTriples, TripleElementId};
-use rand::{Rng, SeedableRng};
-use std::collections::HashSet;
+use crate::rdf::triple_compressor::{CompressedRdfTriples, TripleElementId, TripleSet};
+use clap::ArgEnum;
+use rand::{
+    distributions::{Distribution, WeightedError, WeightedIndex},
+    rngs::SmallRng,
+    seq::SliceRandom,
+    Rng,
+};
+use serde::Deserialize;
+use std::{
+    borrow::Borrow,
+    collections::{btree_map, BTreeMap, BTreeSet, HashMap, HashSet, VecDeque},
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+/// How many entities [`entity_triple_generator`] looks ahead to find one that still fits into a query
+const ENTITY_LOOKAHEAD: usize = 1024;
+
+/// How many consecutive already used triples [`degree_biased_triple_generator`] draws before giving up on a query
+const MAX_CONSECUTIVE_SAMPLING_FAILURES: usize = 10_000;
+
+/// What [`fixed_size_changeset_triple_generator`] does once every changeset has been used
+#[derive(Copy, Clone, Default, ArgEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ChangesetExhaustion {
+    /// start again with the first changeset, i.e. reuse triples of earlier queries.
+    /// A single query still never contains a triple twice
+    #[default]
+    Wrap,
+
+    /// stop generating triples, the remaining queries are smaller than requested or empty
+    Stop,
+}
+
+/// A triple yielded by a triple generator
+pub trait GeneratedTriple: Borrow<[TripleElementId; 3]> {
+    /// The file the triple was taken from, if it was not taken from the main dataset
+    fn source_file(&self) -> Option<&Path> {
+        None
+    }
+}
+
+impl GeneratedTriple for &[TripleElementId; 3] {}
+
+/// A compressed changeset together with the file it was loaded from
+pub struct Changeset {
+    pub path: PathBuf,
+    pub triples: CompressedRdfTriples,
+}
+
+/// A triple taken from a [`Changeset`]
+#[derive(Clone, Copy)]
+pub struct ChangesetTriple<'c> {
+    pub changeset: &'c Path,
+    pub triple: &'c [TripleElementId; 3],
+}
+
+impl Borrow<[TripleElementId; 3]> for ChangesetTriple<'_> {
+    fn borrow(&self) -> &[TripleElementId; 3] {
+        self.triple
+    }
+}
+
+impl GeneratedTriple for ChangesetTriple<'_> {
+    fn source_file(&self) -> Option<&Path> {
+        Some(self.changeset)
+    }
+}
+
+impl Changeset {
+    fn generated_triples(&self) -> impl Iterator<Item = ChangesetTriple<'_>> + Send {
+        self.triples.iter().map(|triple| ChangesetTriple { changeset: &self.path, triple })
+    }
+}
+
+fn group_ranges<K, F>(triples: &[[TripleElementId; 3]], key: F) -> Vec<Range<usize>>
+where
+    K: Eq,
+    F: Fn(&[TripleElementId; 3]) -> K,
+{
+    let mut ranges = Vec::new();
+    let mut start = 0;
+
+    for ix in 1..=triples.len() {
+        if ix == triples.len() || key(&triples[ix]) != key(&triples[start]) {
+            ranges.push(start..ix);
+            start = ix;
+        }
+    }
+
+    ranges
+}
+
+/// Index ranges of the runs of triples with the same subject in a sorted dataset
+pub fn subject_ranges(triples: &CompressedRdfTriples) -> Vec<Range<usize>> {
+    group_ranges(triples, |&[s, _, _]| s)
+}
+
+/// Index ranges of the runs of triples with the same subject and predicate in a sorted dataset
+pub fn subject_predicate_ranges(triples: &CompressedRdfTriples) -> Vec<Range<usize>> {
+    group_ranges(triples, |&[s, p, _]| (s, p))
+}
 
 pub fn random_distinct_triple_generator<'a>(
     triples: &'a CompressedRdfTriples,
     n_total_query_triples: usize,
+    mut rng: SmallRng,
 ) -> impl FnMut(usize) -> Box<dyn Iterator<Item = &'a [TripleElementId; 3]> + Send + 'a> {
-    let mut rng = rand::rngs::SmallRng::from_entropy();
     let mut itr = rand::seq::index::sample(&mut rng, triples.len(), n_total_query_triples).into_iter();
 
     move |size_hint: usize| {
-        let ret = itr.clone().take(size_hint).map(|ix| &triples[ix]);
+        // every query only keeps its own share of the sampled indices
+        let ixs: Vec<_> = itr.by_ref().take(size_hint).collect();
 
-        let _ = itr.advance_by(size_hint);
+        Box::new(ixs.into_iter().map(|ix| &triples[ix]))
+    }
+}
+
+/// Like [`random_distinct_triple_generator`] but yields whole groups of triples (see [`subject_ranges`])
+/// instead of single triples
+pub fn random_distinct_group_generator<'a>(
+    triples: &'a CompressedRdfTriples,
+    groups: &'a [Range<usize>],
+    n_total_query_groups: usize,
+    mut rng: SmallRng,
+) -> impl FnMut(usize) -> Box<dyn Iterator<Item = &'a [[TripleElementId; 3]]> + Send + 'a> {
+    let mut itr = rand::seq::index::sample(&mut rng, groups.len(), n_total_query_groups.min(groups.len())).into_iter();
 
-        Box::new(ret)
+    move |size_hint: usize| {
+        let ixs: Vec<_> = itr.by_ref().take(size_hint).collect();
+
+        Box::new(ixs.into_iter().map(|ix| &triples[groups[ix].clone()]))
     }
 }
 
-pub fn random_triple_generator<'a>(
+/// Generates queries consisting of whole entities, i.e. all triples of a subject, sampled without replacement
+/// from a sorted dataset.
+///
+/// Entities that do not fit into the remaining space of a query are skipped and kept for later queries.
+/// If `allow_partial` is set and no fitting entity is found, the query is filled up with the first triples
+/// of the next entity and the rest of that entity starts the following query.
+/// Otherwise the query stays smaller than requested.
+pub fn entity_triple_generator<'a>(
     triples: &'a CompressedRdfTriples,
+    allow_partial: bool,
+    mut rng: SmallRng,
 ) -> impl FnMut(usize) -> Box<dyn Iterator<Item = &'a [TripleElementId; 3]> + Send + 'a> {
-    |size_hint: usize| {
-        let mut rng = rand::rngs::SmallRng::from_entropy();
+    let mut entities = subject_ranges(triples);
+    entities.shuffle(&mut rng);
+
+    let mut cursor = 0;
+    let mut partial_rest: Option<Range<usize>> = None;
+
+    move |size_hint: usize| {
+        let mut selected = Vec::new();
+        let mut remaining = size_hint;
+
+        if let Some(rest) = partial_rest.take() {
+            if rest.len() <= remaining {
+                remaining -= rest.len();
+                selected.push(rest);
+            } else {
+                let split = rest.start + remaining;
+                selected.push(rest.start..split);
+                partial_rest = Some(split..rest.end);
+                remaining = 0;
+            }
+        }
+
+        while remaining > 0 && cursor < entities.len() {
+            let lookahead_end = entities.len().min(cursor + ENTITY_LOOKAHEAD);
+
+            match (cursor..lookahead_end).find(|&ix| entities[ix].len() <= remaining) {
+                Some(ix) => {
+                    entities.swap(cursor, ix);
+
+                    let entity = entities[cursor].clone();
+                    cursor += 1;
+
+                    remaining -= entity.len();
+                    selected.push(entity);
+                },
+                None if allow_partial => {
+                    let entity = entities[cursor].clone();
+                    cursor += 1;
+
+                    let split = entity.start + remaining;
+                    selected.push(entity.start..split);
+                    partial_rest = Some(split..entity.end);
+                    remaining = 0;
+                },
+                None => break,
+            }
+        }
+
+        Box::new(selected.into_iter().flat_map(move |range| triples[range].iter()))
+    }
+}
+
+struct PredicateStratum {
+    weight: f64,
+    /// indices of the triples with this predicate, the ones before `cursor` are already used
+    triple_indices: Vec<usize>,
+    cursor: usize,
+}
+
+impl PredicateStratum {
+    fn n_available(&self) -> usize {
+        self.triple_indices.len() - self.cursor
+    }
+}
+
+/// Distributes `n` over the strata proportionally to their weights without exceeding their capacities.
+/// Whatever cannot be distributed proportionally is handed out one by one to distinct strata,
+/// chosen randomly weighted by their weights.
+fn allocate_quotas<R: Rng>(weights: &[f64], capacities: &[usize], n: usize, rng: &mut R) -> Vec<usize> {
+    let mut quotas = vec![0; weights.len()];
+    let mut remaining = n;
 
+    while remaining > 0 {
+        let active: Vec<usize> = (0..weights.len())
+            .filter(|&ix| weights[ix] > 0.0 && quotas[ix] < capacities[ix])
+            .collect();
+
+        if active.is_empty() {
+            break;
+        }
+
+        let total_weight: f64 = active.iter().map(|&ix| weights[ix]).sum();
+        let mut assigned = 0;
+
+        for &ix in &active {
+            let share = (remaining as f64 * weights[ix] / total_weight).floor() as usize;
+            let share = share.min(capacities[ix] - quotas[ix]);
+
+            quotas[ix] += share;
+            assigned += share;
+        }
+
+        remaining -= assigned;
+
+        if assigned == 0 {
+            let chosen: Vec<usize> = active
+                .choose_multiple_weighted(rng, remaining.min(active.len()), |&ix| weights[ix])
+                .expect("stratum weights to be valid")
+                .copied()
+                .collect();
+
+            for ix in chosen {
+                quotas[ix] += 1;
+                remaining -= 1;
+            }
+        }
+    }
+
+    quotas
+}
+
+/// Generates distinct triples stratified by predicate.
+///
+/// Every query is split among the predicates of the dataset proportionally to their weight in `predicate_weights`,
+/// predicates missing from the table are never selected. Without a weight table every predicate gets an equal share.
+/// Within a predicate triples are sampled uniformly and without replacement.
+pub fn predicate_stratified_triple_generator<'a>(
+    triples: &'a CompressedRdfTriples,
+    predicate_weights: Option<&HashMap<TripleElementId, f64>>,
+    mut rng: SmallRng,
+) -> impl FnMut(usize) -> Box<dyn Iterator<Item = &'a [TripleElementId; 3]> + Send + 'a> {
+    let mut strata: Vec<_> = {
+        let mut by_predicate: HashMap<TripleElementId, Vec<usize>> = HashMap::new();
+
+        for (ix, &[_, p, _]) in triples.iter().enumerate() {
+            by_predicate.entry(p).or_default().push(ix);
+        }
+
+        by_predicate.into_iter().collect()
+    };
+
+    // sort to be independent of the hash map iteration order
+    strata.sort_unstable_by_key(|(p, _)| *p);
+
+    if let Some(weights) = predicate_weights {
+        let n_unmatched = weights.keys().filter(|p| strata.binary_search_by_key(p, |(q, _)| q).is_err()).count();
+
+        if n_unmatched > 0 {
+            println!(
+                "Warning: {n_unmatched} of {} weighted predicates do not occur in the main dataset",
+                weights.len()
+            );
+        }
+    }
+
+    let mut strata: Vec<_> = strata
+        .into_iter()
+        .map(|(p, triple_indices)| PredicateStratum {
+            weight: predicate_weights.map_or(1.0, |weights| weights.get(&p).copied().unwrap_or(0.0)),
+            triple_indices,
+            cursor: 0,
+        })
+        .collect();
+
+    let weights: Vec<f64> = strata.iter().map(|stratum| stratum.weight).collect();
+
+    move |size_hint: usize| {
+        let capacities: Vec<usize> = strata.iter().map(PredicateStratum::n_available).collect();
+        let quotas = allocate_quotas(&weights, &capacities, size_hint, &mut rng);
+
+        let mut selected = Vec::with_capacity(size_hint);
+
+        for (stratum, quota) in strata.iter_mut().zip(quotas) {
+            for _ in 0..quota {
+                let ix = rng.gen_range(stratum.cursor..stratum.triple_indices.len());
+                stratum.triple_indices.swap(stratum.cursor, ix);
+
+                selected.push(stratum.triple_indices[stratum.cursor]);
+                stratum.cursor += 1;
+            }
+        }
+
+        Box::new(selected.into_iter().map(move |ix| &triples[ix]))
+    }
+}
+
+/// Generates distinct triples by first selecting a subject with probability proportional to `degree^exponent`
+/// and then a random triple of that subject.
+///
+/// An exponent of 1 is equivalent to uniform triple sampling, larger exponents favour high degree subjects,
+/// smaller exponents favour low degree subjects (0 makes every subject equally likely).
+/// Fails if the dataset is empty or the exponent does not result in usable subject weights.
+pub fn degree_biased_triple_generator<'a>(
+    triples: &'a CompressedRdfTriples,
+    exponent: f64,
+    mut rng: SmallRng,
+) -> Result<impl FnMut(usize) -> Box<dyn Iterator<Item = &'a [TripleElementId; 3]> + Send + 'a>, String> {
+    if !exponent.is_finite() {
+        return Err(format!("exponent must be finite, got {exponent}"));
+    }
+
+    let subjects = subject_ranges(triples);
+    if subjects.is_empty() {
+        return Err("cannot select subjects from an empty dataset".to_owned());
+    }
+
+    let subject_weights: Vec<f64> = subjects.iter().map(|range| (range.len() as f64).powf(exponent)).collect();
+    if !subject_weights.iter().sum::<f64>().is_finite() {
+        return Err(format!("exponent {exponent} is too large, the subject weights overflow"));
+    }
+
+    let subject_distribution = WeightedIndex::new(&subject_weights).map_err(|e| match e {
+        WeightedError::AllWeightsZero => format!("exponent {exponent} is too small, all subject weights are 0"),
+        e => format!("invalid subject weights for exponent {exponent}: {e}"),
+    })?;
+
+    let mut used = HashSet::new();
+
+    Ok(move |size_hint: usize| {
+        let mut selected = Vec::with_capacity(size_hint);
+        let mut n_failures = 0;
+
+        while selected.len() < size_hint && n_failures < MAX_CONSECUTIVE_SAMPLING_FAILURES {
+            let subject = &subjects[subject_distribution.sample(&mut rng)];
+            let ix = rng.gen_range(subject.clone());
+
+            if used.insert(ix) {
+                selected.push(ix);
+                n_failures = 0;
+            } else {
+                n_failures += 1;
+            }
+        }
+
+        Box::new(selected.into_iter().map(move |ix| &triples[ix])) as Box<dyn Iterator<Item = _> + Send>
+    })
+}
+
+pub fn random_triple_generator<'a>(
+    triples: &'a CompressedRdfTriples,
+    mut rng: SmallRng,
+) -> impl FnMut(usize) -> Box<dyn Iterator<Item = &'a [TripleElementId; 3]> + Send + 'a> {
+    move |size_hint: usize| {
         let itr = rand::seq::index::sample(&mut rng, triples.len(), size_hint)
             .into_iter()
             .map(|ix| &triples[ix]);
@@ -32,58 +387,548 @@ pub fn random_triple_generator<'a>(
     }
 }
 
-pub fn fixed_size_changeset_triple_generator<'a, 'c, 'd>(
-    changesets: &'c [CompressedRdfTriples],
+/// Generates queries of exactly the requested size by walking through the changesets in order,
+/// truncating and stitching them together at query boundaries.
+///
+/// Every query continues where the previous one stopped, so no triple is used twice
+/// until all changesets are exhausted. Triples that are not contained in `dataset` are skipped.
+/// A query never wraps around past its own first triple, so it contains every triple at most once.
+///
+/// If `max_body_len` is given, a query also ends before the first triple that would exceed it (as measured
+/// by `triple_len`), which is left for the next query. A single triple larger than `max_body_len`
+/// can never fit and is yielded on its own.
+pub fn fixed_size_changeset_triple_generator<'a, 'c, 'd, F>(
+    changesets: &'c [Changeset],
     dataset: &'d CompressedRdfTriples,
-) -> impl FnMut(usize) -> Box<dyn Iterator<Item = &'c [TripleElementId; 3]> + Send + 'a>
+    when_exhausted: ChangesetExhaustion,
+    max_body_len: Option<usize>,
+    triple_len: F,
+) -> impl FnMut(usize) -> Box<dyn Iterator<Item = ChangesetTriple<'c>> + Send + 'a>
 where
     'c: 'a,
     'd: 'a,
+    F: Fn(&[TripleElementId; 3]) -> usize,
 {
-    let start_off = rand::thread_rng().gen_range(0..changesets.len());
+    // position of the next unused triple
+    let mut changeset_ix = 0;
+    let mut triple_ix = 0;
+    let mut exhausted = false;
 
     move |size_hint: usize| {
-        let itr = changesets[start_off..]
-            .iter()
-            .chain(changesets[..start_off].iter().rev())
-            .flat_map(|compressed_triples| compressed_triples.iter())
-            .filter(|triple| dataset.contains(triple))
-            .take(size_hint);
+        let mut selected = Vec::with_capacity(size_hint);
+        let mut body_len = 0;
 
-        Box::new(itr)
+        let start = (changeset_ix, triple_ix);
+        let mut wrapped = false;
+
+        while selected.len() < size_hint && !exhausted {
+            if wrapped && (changeset_ix == changesets.len() || (changeset_ix, triple_ix) >= start) {
+                // this query has visited every triple once
+                if selected.is_empty() {
+                    println!("Warning: the changesets do not contain any triples of the main dataset");
+                    exhausted = true;
+                }
+
+                break;
+            }
+
+            let Some(changeset) = changesets.get(changeset_ix) else {
+                match when_exhausted {
+                    ChangesetExhaustion::Stop => {
+                        println!("Warning: all changesets are used up, the remaining queries are incomplete");
+                        exhausted = true;
+                    },
+                    ChangesetExhaustion::Wrap => {
+                        println!("Warning: all changesets are used up, reusing them from the start");
+                        changeset_ix = 0;
+                        triple_ix = 0;
+                        wrapped = true;
+                    },
+                }
+
+                continue;
+            };
+
+            let Some(triple) = changeset.triples.get(triple_ix) else {
+                changeset_ix += 1;
+                triple_ix = 0;
+                continue;
+            };
+
+            if !dataset.contains(triple) {
+                triple_ix += 1;
+                continue;
+            }
+
+            if let Some(max_body_len) = max_body_len {
+                let len = triple_len(triple);
+
+                if body_len + len > max_body_len && !selected.is_empty() {
+                    break;
+                }
+
+                body_len += len;
+            }
+
+            selected.push(ChangesetTriple { changeset: &changeset.path, triple });
+            triple_ix += 1;
+        }
+
+        Box::new(selected.into_iter())
     }
 }
 
-pub fn as_is_changeset_triple_generator<'c>(
-    changesets: &'c [CompressedRdfTriples],
-) -> impl FnMut(usize) -> Box<dyn Iterator<Item = &'c [TripleElementId; 3]> + Send + 'c> {
+/// Changesets by their size, for finding the changeset closest to a requested size
+#[derive(Default)]
+struct ChangesetSizeIndex {
+    /// changeset indices by size, the indices of one size are kept sorted for deterministic selection
+    by_size: BTreeMap<usize, BTreeSet<usize>>,
+}
+
+impl ChangesetSizeIndex {
+    fn insert(&mut self, size: usize, ix: usize) {
+        self.by_size.entry(size).or_default().insert(ix);
+    }
+
+    fn remove(&mut self, size: usize, ix: usize) {
+        if let btree_map::Entry::Occupied(mut entry) = self.by_size.entry(size) {
+            entry.get_mut().remove(&ix);
+
+            if entry.get().is_empty() {
+                entry.remove();
+            }
+        }
+    }
+
+    /// The changeset closest to `size` as `(distance, size, index)`, on ties the one with the lowest index
+    fn nearest(&self, size: usize) -> Option<(usize, usize, usize)> {
+        let below = self.by_size.range(..=size).next_back();
+        let above = self.by_size.range(size..).next();
+
+        [below, above]
+            .into_iter()
+            .flatten()
+            .map(|(&candidate_size, ixs)| {
+                let ix = *ixs.first().expect("empty sizes to be removed");
+                (candidate_size.abs_diff(size), candidate_size, ix)
+            })
+            .min_by_key(|&(distance, _, ix)| (distance, ix))
+    }
+}
+
+/// What [`as_is_changeset_triple_generator`] does with changesets that were already used for a query
+#[derive(Copy, Clone, Default, ArgEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ChangesetReuse {
+    /// every changeset is used at most once, running out of changesets is an error
+    #[default]
+    Never,
+
+    /// used changesets stay available and are chosen like unused ones
+    Always,
+
+    /// used changesets stay available, but every earlier use counts as a size difference
+    /// of the reuse penalty times the requested size
+    Penalized,
+}
+
+/// How [`as_is_changeset_triple_generator`] selects changesets
+#[derive(Copy, Clone)]
+pub struct ChangesetSelection {
+    pub reuse: ChangesetReuse,
+
+    /// for [`ChangesetReuse::Penalized`], the size difference (relative to the requested size)
+    /// that every earlier use counts as
+    pub reuse_penalty: f64,
+
+    /// changesets whose size differs by more than this fraction of the requested size are never selected
+    pub size_tolerance: Option<f64>,
+}
+
+/// Selects a changeset for every query size in `query_sizes`, preferring the changeset whose size is closest
+/// to the requested size (see [`ChangesetSelection`]). The sizes are looked up in a size index,
+/// so the selection takes logarithmic time in the number of changesets.
+fn select_changesets(
+    sizes: &[usize],
+    query_sizes: impl IntoIterator<Item = usize>,
+    ChangesetSelection { reuse, reuse_penalty, size_tolerance }: ChangesetSelection,
+) -> Result<Vec<usize>, String> {
+    if !(0.0..).contains(&reuse_penalty) {
+        return Err(format!("reuse penalty must not be negative, got {reuse_penalty}"));
+    }
+
+    if let Some(tolerance) = size_tolerance.filter(|tolerance| !(0.0..).contains(tolerance)) {
+        return Err(format!("size tolerance must not be negative, got {tolerance}"));
+    }
+
+    // for penalized reuse tier k holds the changesets that were used k times, otherwise there is only one tier
+    let mut tiers = vec![ChangesetSizeIndex::default()];
+    for (ix, &size) in sizes.iter().enumerate() {
+        tiers[0].insert(size, ix);
+    }
+
+    let mut selected = Vec::new();
     let mut used = HashSet::new();
+    let mut n_reused = 0;
 
-    move |size_hint: usize| {
-        let (used_ix, changeset) = changesets
-            .iter()
-            .enumerate()
-            .filter(|(ix, _)| !used.contains(ix))
-            .min_by_key(|(_, triples)| triples.len().abs_diff(size_hint))
-            .expect("more than 0 changesets");
+    for (query, query_size) in query_sizes.into_iter().enumerate() {
+        let max_distance = size_tolerance.map(|tolerance| tolerance * query_size as f64);
+        let tier_penalty = reuse_penalty * query_size as f64;
+
+        let mut best: Option<(f64, usize, usize, usize)> = None;
+
+        // distance of the closest available changeset, whether or not it is within the size tolerance
+        let mut closest_distance: Option<usize> = None;
+
+        for (n_uses, tier) in tiers.iter().enumerate() {
+            let penalty = n_uses as f64 * tier_penalty;
+
+            // the distance can only add to the penalty, so later tiers cannot be better
+            if matches!(best, Some((cost, ..)) if penalty > cost) {
+                break;
+            }
+
+            let Some((distance, size, ix)) = tier.nearest(query_size) else {
+                continue;
+            };
+
+            closest_distance = Some(closest_distance.map_or(distance, |closest| closest.min(distance)));
+
+            if matches!(max_distance, Some(max_distance) if distance as f64 > max_distance) {
+                continue;
+            }
+
+            let cost = distance as f64 + penalty;
+            if !matches!(best, Some((best_cost, _, _, best_ix)) if (best_cost, best_ix) <= (cost, ix)) {
+                best = Some((cost, n_uses, size, ix));
+            }
+        }
+
+        let Some((_, n_uses, size, ix)) = best else {
+            return Err(match (closest_distance, size_tolerance, reuse) {
+                (Some(closest_distance), Some(tolerance), _) => format!(
+                    "no {}changeset within a size tolerance of {tolerance} for query {query} of size {query_size}, \
+                     the closest one differs by {closest_distance} triples",
+                    if let ChangesetReuse::Never = reuse { "unused " } else { "" }
+                ),
+                (_, _, ChangesetReuse::Never) if !sizes.is_empty() => format!(
+                    "ran out of changesets at query {query}, only {} changesets available without reuse",
+                    sizes.len()
+                ),
+                _ => "no changesets available".to_owned(),
+            });
+        };
+
+        match reuse {
+            ChangesetReuse::Never => tiers[n_uses].remove(size, ix),
+            ChangesetReuse::Always => (),
+            ChangesetReuse::Penalized => {
+                tiers[n_uses].remove(size, ix);
 
-        println!("using changeset: {used_ix}");
+                if tiers.len() == n_uses + 1 {
+                    tiers.push(ChangesetSizeIndex::default());
+                }
 
-        used.insert(used_ix);
+                tiers[n_uses + 1].insert(size, ix);
+            },
+        }
 
-        Box::new(changeset.iter())
+        if !used.insert(ix) {
+            n_reused += 1;
+        }
+
+        selected.push(ix);
+    }
+
+    if n_reused > 0 {
+        println!("{n_reused} queries reuse an already used changeset");
+    }
+
+    Ok(selected)
+}
+
+/// Yields whole changesets, for every query the changeset whose size is closest to the requested size.
+/// Triples in `excluded` are skipped and do not count towards the size of a changeset.
+///
+/// The changesets are selected up front for `query_sizes`, which must be the sizes the generator is then called with
+/// (in any order), so that running out of fitting changesets is reported before any query is written.
+pub fn as_is_changeset_triple_generator<'c>(
+    changesets: &'c [Changeset],
+    excluded: &'c TripleSet,
+    query_sizes: impl IntoIterator<Item = usize>,
+    selection: ChangesetSelection,
+) -> Result<impl FnMut(usize) -> Box<dyn Iterator<Item = ChangesetTriple<'c>> + Send + 'c>, String> {
+    let sizes: Vec<_> = changesets
+        .iter()
+        .map(|changeset| changeset.triples.iter().filter(|triple| !excluded.contains(*triple)).count())
+        .collect();
+
+    let query_sizes: Vec<_> = query_sizes.into_iter().collect();
+    let selected = select_changesets(&sizes, query_sizes.iter().copied(), selection)?;
+
+    // the queries may be generated in a different order than selected for, so the selected changesets
+    // are handed out by requested size (in selection order for every size)
+    let mut selected_by_size: HashMap<usize, VecDeque<usize>> = HashMap::new();
+    for (size, ix) in query_sizes.into_iter().zip(selected) {
+        selected_by_size.entry(size).or_default().push_back(ix);
     }
+
+    Ok(move |size_hint: usize| {
+        let ix = selected_by_size
+            .get_mut(&size_hint)
+            .and_then(VecDeque::pop_front)
+            .expect("a changeset to be selected for every query");
+
+        Box::new(changesets[ix].generated_triples().filter(|triple| !excluded.contains(triple.triple))) as _
+    })
 }
 
 pub fn linear_changeset_triple_generator<'c>(
-    changesets: &'c [CompressedRdfTriples],
-) -> impl Iterator<Item = Box<dyn Iterator<Item = &'c [TripleElementId; 3]> + Send + 'c>> {
+    changesets: &'c [Changeset],
+) -> impl Iterator<Item = Box<dyn Iterator<Item = ChangesetTriple<'c>> + Send + 'c>> {
     let mut cur = 0;
 
     std::iter::from_fn(move || {
-        let ret = changesets.get(cur).map(|chs| Box::new(chs.iter()) as _);
+        let ret = changesets.get(cur).map(|chs| Box::new(chs.generated_triples()) as _);
         cur += 1;
 
         ret
     })
 }
+
+/// Describes a piece of a replicated dataset, e.g. `changesets/0001.compressed_nt [0..1000]`
+fn describe_piece(changeset: &Changeset, range: &Range<usize>) -> String {
+    if *range == (0..changeset.triples.len()) {
+        changeset.path.display().to_string()
+    } else {
+        format!("{} [{}..{}]", changeset.path.display(), range.start, range.end)
+    }
+}
+
+/// Like [`linear_changeset_triple_generator`] but splits changesets into queries of at most `max_triples` triples
+/// and `max_body_len` bytes, as measured by `triple_len`. A single triple larger than `max_body_len`
+/// becomes a query of its own. If `merge` is set, following changesets are added to a query as long as they fit
+/// into these limits as a whole. Every query comes with a description of the changesets (and triple ranges)
+/// it was taken from.
+pub fn bounded_changeset_triple_generator<'c, F>(
+    changesets: &'c [Changeset],
+    max_triples: Option<usize>,
+    max_body_len: Option<usize>,
+    merge: bool,
+    triple_len: F,
+) -> impl Iterator<Item = (String, Box<dyn Iterator<Item = ChangesetTriple<'c>> + Send + 'c>)>
+where
+    F: Fn(&[TripleElementId; 3]) -> usize,
+{
+    // position of the next triple
+    let mut changeset_ix = 0;
+    let mut triple_ix = 0;
+
+    std::iter::from_fn(move || {
+        let mut selected = Vec::new();
+        let mut body_len = 0;
+        let mut pieces: Vec<(usize, Range<usize>)> = Vec::new();
+
+        while let Some(changeset) = changesets.get(changeset_ix) {
+            if triple_ix == changeset.triples.len() {
+                changeset_ix += 1;
+                triple_ix = 0;
+
+                if !merge && pieces.is_empty() && changeset.triples.is_empty() {
+                    // every changeset results in at least one query, even an empty one
+                    pieces.push((changeset_ix - 1, 0..0));
+                }
+
+                if !merge && !pieces.is_empty() {
+                    break;
+                }
+
+                continue;
+            }
+
+            if triple_ix == 0 && !pieces.is_empty() {
+                // only whole changesets are merged into a query that already has triples
+                let exceeds_triples = matches!(
+                    max_triples,
+                    Some(max_triples) if selected.len() + changeset.triples.len() > max_triples
+                );
+                let exceeds_body_len = matches!(
+                    max_body_len,
+                    Some(max_body_len) if changeset
+                        .triples
+                        .iter()
+                        .try_fold(body_len, |len, triple| {
+                            Some(len + triple_len(triple)).filter(|&len| len <= max_body_len)
+                        })
+                        .is_none()
+                );
+
+                if exceeds_triples || exceeds_body_len {
+                    break;
+                }
+            }
+
+            let triple = &changeset.triples[triple_ix];
+            let len = max_body_len.map_or(0, |_| triple_len(triple));
+
+            let is_full = matches!(max_triples, Some(max_triples) if selected.len() >= max_triples)
+                || matches!(max_body_len, Some(max_body_len) if body_len + len > max_body_len);
+
+            if is_full && !selected.is_empty() {
+                break;
+            }
+
+            if is_full {
+                println!(
+                    "Warning: a triple of {:?} exceeds the maximum query size, it gets a query of its own",
+                    changeset.path
+                );
+            }
+
+            match pieces.last_mut() {
+                Some((ix, range)) if *ix == changeset_ix => range.end += 1,
+                _ => pieces.push((changeset_ix, triple_ix..triple_ix + 1)),
+            }
+
+            selected.push(ChangesetTriple { changeset: &changeset.path, triple });
+            body_len += len;
+            triple_ix += 1;
+        }
+
+        let source = match pieces.as_slice() {
+            [] => return None,
+            [(ix, range)] => describe_piece(&changesets[*ix], range),
+            [(ix, range), rest @ ..] => {
+                format!("{} and {} more", describe_piece(&changesets[*ix], range), rest.len())
+            },
+        };
+
+        Some((source, Box::new(selected.into_iter()) as _))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn triple(n: TripleElementId) -> [TripleElementId; 3] {
+        [n, 0, n]
+    }
+
+    fn changeset(name: &str, triples: &[[TripleElementId; 3]]) -> Changeset {
+        Changeset { path: PathBuf::from(name), triples: CompressedRdfTriples::from_triples(name, triples) }
+    }
+
+    fn selection(reuse: ChangesetReuse, size_tolerance: Option<f64>) -> ChangesetSelection {
+        ChangesetSelection { reuse, reuse_penalty: 0.5, size_tolerance }
+    }
+
+    #[test]
+    fn fixed_size_queries_never_wrap_past_their_start() {
+        let changesets = [changeset("wrap-0", &[triple(1), triple(2)]), changeset("wrap-1", &[triple(3)])];
+        let dataset = changeset("wrap-dataset", &[triple(1), triple(2), triple(3)]);
+
+        let when_exhausted = ChangesetExhaustion::Wrap;
+        let mut generator =
+            fixed_size_changeset_triple_generator(&changesets, &dataset.triples, when_exhausted, None, |_| 0);
+        let mut query = |size| generator(size).map(|triple| triple.triple[0]).collect::<Vec<_>>();
+
+        assert_eq!(query(2), [1, 2]);
+        assert_eq!(query(5), [3, 1, 2]);
+        assert_eq!(query(1), [3]);
+    }
+
+    #[test]
+    fn fixed_size_queries_leave_the_cut_triple_for_the_next_query() {
+        let changesets = [changeset("cut-0", &[triple(1), triple(2), triple(3)]), changeset("cut-1", &[triple(4)])];
+        let dataset = changeset("cut-dataset", &[triple(1), triple(2), triple(3), triple(4)]);
+
+        let when_exhausted = ChangesetExhaustion::Stop;
+        let mut generator =
+            fixed_size_changeset_triple_generator(&changesets, &dataset.triples, when_exhausted, Some(25), |_| 10);
+        let mut query = |size| generator(size).map(|triple| triple.triple[0]).collect::<Vec<_>>();
+
+        assert_eq!(query(3), [1, 2]);
+        assert_eq!(query(3), [3, 4]);
+        assert!(query(3).is_empty());
+    }
+
+    #[test]
+    fn changesets_are_selected_by_distance_without_reuse() {
+        let selected = select_changesets(&[2, 5, 3], [3, 3, 3], selection(ChangesetReuse::Never, None));
+        assert_eq!(selected, Ok(vec![2, 0, 1]));
+    }
+
+    #[test]
+    fn penalized_reuse_prefers_less_used_changesets() {
+        // the second query prefers the unused changeset 4 triples off over reusing the exact one (penalty 5),
+        // the third then reuses the exact one rather than the more distant one with the same number of uses
+        let selected = select_changesets(&[10, 14], [10, 10, 10], selection(ChangesetReuse::Penalized, None));
+        assert_eq!(selected, Ok(vec![0, 1, 0]));
+
+        let selected = select_changesets(&[10, 14], [10, 10, 10], selection(ChangesetReuse::Always, None));
+        assert_eq!(selected, Ok(vec![0, 0, 0]));
+    }
+
+    #[test]
+    fn missing_the_size_tolerance_is_not_running_out() {
+        let err = select_changesets(&[10], [5], selection(ChangesetReuse::Never, Some(0.1))).unwrap_err();
+        assert!(err.contains("size tolerance of 0.1") && err.contains("differs by 5 triples"), "{err}");
+
+        let err = select_changesets(&[10], [10, 10], selection(ChangesetReuse::Never, None)).unwrap_err();
+        assert!(err.starts_with("ran out of changesets at query 1"), "{err}");
+
+        let err = select_changesets(&[], [10], selection(ChangesetReuse::Always, None)).unwrap_err();
+        assert_eq!(err, "no changesets available");
+    }
+
+    fn bounded_queries(
+        changesets: &[Changeset],
+        max_triples: Option<usize>,
+        max_body_len: Option<usize>,
+        merge: bool,
+    ) -> Vec<(String, Vec<TripleElementId>)> {
+        bounded_changeset_triple_generator(changesets, max_triples, max_body_len, merge, |_| 10)
+            .map(|(source, triples)| (source, triples.map(|triple| triple.triple[0]).collect()))
+            .collect()
+    }
+
+    #[test]
+    fn bounded_queries_merge_whole_datasets_only() {
+        let changesets = [
+            changeset("a", &[triple(1), triple(2)]),
+            changeset("b", &[triple(3)]),
+            changeset("c", &[triple(4), triple(5), triple(6)]),
+            changeset("d", &[triple(7)]),
+        ];
+
+        let query = |source: &str, triples: &[TripleElementId]| (source.to_owned(), triples.to_vec());
+
+        assert_eq!(
+            bounded_queries(&changesets, Some(3), None, true),
+            [query("a and 1 more", &[1, 2, 3]), query("c", &[4, 5, 6]), query("d", &[7])]
+        );
+
+        // b and c only fit into the limits when split, so they are not merged into the query before them
+        assert_eq!(
+            bounded_queries(&changesets, Some(2), None, true),
+            [query("a", &[1, 2]), query("b", &[3]), query("c [0..2]", &[4, 5]), query("c [2..3] and 1 more", &[6, 7])]
+        );
+
+        assert_eq!(
+            bounded_queries(&changesets, None, Some(25), true),
+            [query("a", &[1, 2]), query("b", &[3]), query("c [0..2]", &[4, 5]), query("c [2..3] and 1 more", &[6, 7])]
+        );
+
+        assert_eq!(
+            bounded_queries(&changesets, Some(2), None, false),
+            [
+                query("a", &[1, 2]),
+                query("b", &[3]),
+                query("c [0..2]", &[4, 5]),
+                query("c [2..3]", &[6]),
+                query("d", &[7])
+            ]
+        );
+    }
+}
@@ -0,0 +1,88 @@
+//! Injection of "miss" triples, i.e. triples that are not contained in the main dataset,
+//! into delete queries
+
+use crate::rdf::triple_compressor::{CompressedRdfTriples, TripleElementId, TripleSet};
+use rand::{rngs::SmallRng, Rng};
+
+/// Number of random candidates that are tried per miss triple before giving up
+const MAX_ATTEMPTS_PER_MISS: usize = 1000;
+
+pub enum MissSource<'d> {
+    /// subjects, predicates and objects of random triples of the main dataset are recombined
+    Recombined,
+
+    /// random triples of another dataset
+    Dataset(&'d CompressedRdfTriples),
+}
+
+/// How many and which miss triples are injected into every query
+pub struct Misses<'d> {
+    /// fraction of the triples of every query that are replaced by miss triples
+    pub ratio: f64,
+
+    pub source: MissSource<'d>,
+
+    /// the full main dataset (including excluded triples), miss triples are never contained in it
+    pub dataset: &'d CompressedRdfTriples,
+
+    /// triples that are never used, miss triples are not taken from them either
+    pub excluded: &'d TripleSet,
+
+    pub rng: SmallRng,
+}
+
+impl Misses<'_> {
+    fn random_triple<R: Rng>(triples: &CompressedRdfTriples, rng: &mut R) -> [TripleElementId; 3] {
+        triples[rng.gen_range(0..triples.len())]
+    }
+
+    fn candidate(&mut self) -> Option<[TripleElementId; 3]> {
+        if self.dataset.is_empty() {
+            return None;
+        }
+
+        Some(match self.source {
+            MissSource::Recombined => {
+                let [s, _, _] = Self::random_triple(self.dataset, &mut self.rng);
+                let [_, p, _] = Self::random_triple(self.dataset, &mut self.rng);
+                let [_, _, o] = Self::random_triple(self.dataset, &mut self.rng);
+                [s, p, o]
+            },
+            MissSource::Dataset(triples) if triples.is_empty() => return None,
+            MissSource::Dataset(triples) => Self::random_triple(triples, &mut self.rng),
+        })
+    }
+
+    /// Removes the tail of `triples` and returns the distinct miss triples that replace it
+    pub fn apply<T>(&mut self, triples: &mut Vec<T>) -> Vec<[TripleElementId; 3]> {
+        let n_wanted = (triples.len() as f64 * self.ratio).round() as usize;
+        let mut misses = Vec::with_capacity(n_wanted);
+        let mut seen = TripleSet::default();
+
+        for _ in 0..n_wanted * MAX_ATTEMPTS_PER_MISS {
+            if misses.len() == n_wanted {
+                break;
+            }
+
+            match self.candidate() {
+                Some(triple)
+                    if !self.dataset.contains(&triple) && !self.excluded.contains(&triple) && seen.insert(triple) =>
+                {
+                    misses.push(triple)
+                },
+                Some(_) => (),
+                None => break,
+            }
+        }
+
+        if misses.len() < n_wanted {
+            println!(
+                "Warning: requested {n_wanted} miss triples cannot be fulfilled, only found {}",
+                misses.len()
+            );
+        }
+
+        triples.truncate(triples.len() - misses.len());
+        misses
+    }
+}
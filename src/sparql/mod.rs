@@ -0,0 +1,641 @@
+pub mod miss;
+pub mod output;
+pub mod overlap;
+pub mod replay;
+pub mod serialize;
+pub mod state;
+
+use crate::rdf::{
+    triple_compressor::{decompressor::RdfTripleDecompressor, TripleElementId},
+    triple_generator::GeneratedTriple,
+};
+use clap::ArgEnum;
+use output::{QueryInfo, QueryOutput, QueryWriter};
+use rand::{seq::SliceRandom, Rng};
+use rayon::{
+    iter::{IntoParallelIterator, IntoParallelRefIterator, ParallelIterator},
+    slice::ParallelSlice,
+};
+use miss::Misses;
+use overlap::{Overlap, QueryOverlap};
+use serde::Deserialize;
+use state::StoreSimulation;
+use std::{borrow::Borrow, collections::BTreeSet, io::Write};
+
+/// Upper bound for the number of triples that are decompressed and held in memory at once while writing queries
+const MAX_TRIPLES_IN_FLIGHT: usize = 1 << 20;
+
+/// Upper bound for the number of queries that are generated in parallel before they are written
+const MAX_QUERIES_PER_BATCH: usize = 1024;
+
+/// Number of triples of a single large query that are decompressed as one unit of parallel work
+const DECOMPRESSION_CHUNK_SIZE: usize = 1 << 12;
+
+#[derive(Copy, Clone, Default, ArgEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OutputOrder {
+    #[default]
+    AsSpecified,
+    Randomized,
+    SortedSizeAsc,
+    SortedSizeDesc,
+}
+
+#[derive(Copy, Clone, Default, ArgEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum UpdateKind {
+    /// DELETE DATA queries
+    #[default]
+    Delete,
+
+    /// INSERT DATA queries
+    Insert,
+
+    /// each DELETE DATA query followed by the INSERT DATA query with the same triples, which undoes the deletion.
+    /// Both are separate, consecutively numbered queries, so that the deletion can be timed on its own
+    DeleteInsert,
+}
+
+/// Separates the operations of a single update request
+const OPERATION_SEPARATOR: &str = " ; ";
+
+impl UpdateKind {
+    /// The operation of every query written for a generated query, in order
+    fn operations(self) -> &'static [&'static str] {
+        match self {
+            UpdateKind::Delete => &["DELETE DATA"],
+            UpdateKind::Insert => &["INSERT DATA"],
+            UpdateKind::DeleteInsert => &["DELETE DATA", "INSERT DATA"],
+        }
+    }
+
+    /// The largest body of triples (see [`rendered_triple_len`]) for which every query written for it
+    /// (see [`UpdateKind::operations`]) takes up at most `max_bytes`
+    pub fn max_body_len(self, max_bytes: usize) -> usize {
+        let overhead = self
+            .operations()
+            .iter()
+            .map(|operation| format!("{operation} {{ }}\n").len())
+            .max()
+            .expect("every update kind to have an operation");
+
+        max_bytes.saturating_sub(overhead)
+    }
+}
+
+/// A query spec with the size of each of its queries already drawn
+#[derive(Clone)]
+pub struct QuerySpec {
+    /// the spec as given by the user, e.g. `100x10-1000`
+    pub description: String,
+
+    /// the requested number of triples (or patterns) of every query of the spec
+    pub query_sizes: Vec<usize>,
+}
+
+impl QuerySpec {
+    pub fn n_queries(&self) -> usize {
+        self.query_sizes.len()
+    }
+
+    /// Sum of the requested sizes of all queries of the spec
+    pub fn total_size(&self) -> usize {
+        self.query_sizes.iter().sum()
+    }
+}
+
+#[derive(Copy, Clone, Default, ArgEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PatternKind {
+    /// all triples of a subject, `<s> ?p ?o`
+    #[default]
+    Subject,
+
+    /// all objects of a subject-predicate pair, `<s> <p> ?o`
+    SubjectPredicate,
+}
+
+#[derive(Copy, Clone, Default, ArgEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PatternOperation {
+    /// `DELETE WHERE { pattern }`, removes the matching triples
+    #[default]
+    DeleteWhere,
+
+    /// `DELETE { pattern } INSERT { pattern } WHERE { pattern }`, rewrites the matching triples
+    /// and leaves the store unchanged
+    DeleteInsertWhere,
+}
+
+#[derive(Copy, Clone)]
+pub struct PatternSpec {
+    pub kind: PatternKind,
+    pub operation: PatternOperation,
+}
+
+fn order_generators<G, R: Rng>(generators: &mut [(usize, G)], order: OutputOrder, rng: &mut R) {
+    match order {
+        OutputOrder::AsSpecified => (),
+        OutputOrder::Randomized => generators.shuffle(rng),
+        OutputOrder::SortedSizeAsc => generators.sort_unstable_by_key(|(n_triples, _)| *n_triples),
+        OutputOrder::SortedSizeDesc => generators.sort_unstable_by_key(|(n_triples, _)| std::cmp::Reverse(*n_triples)),
+    }
+}
+
+/// The requested size and spec index of every query in output order. The generators are only created
+/// batch by batch in this order, so that the triples of at most one batch are held in memory at once.
+fn ordered_queries<R: Rng>(query_specs: &[QuerySpec], order: OutputOrder, rng: &mut R) -> Vec<(usize, usize)> {
+    let mut queries: Vec<_> = query_specs
+        .iter()
+        .enumerate()
+        .flat_map(|(spec, QuerySpec { query_sizes, .. })| query_sizes.iter().map(move |&size| (size, spec)))
+        .collect();
+
+    order_generators(&mut queries, order, rng);
+    queries
+}
+
+/// Adjustments that are applied to every generated query in output order before it is written,
+/// in the order misses, overlap, simulation
+pub struct QueryAdjustments<'d> {
+    /// replace part of every query with triples that are not contained in the main dataset
+    pub misses: Option<Misses<'d>>,
+
+    /// replace part of every query with triples of earlier queries, not counting the misses
+    pub overlap: Option<Overlap>,
+
+    /// run every query against the simulated store, see [`state::StateTracking`]
+    pub simulation: Option<StoreSimulation<'d>>,
+
+    /// stop adding triples to a query once the whole query would take up more than this many bytes,
+    /// only without misses and overlap as these replace triples afterwards
+    pub max_bytes: Option<usize>,
+}
+
+/// A generated but not yet decompressed query
+struct GeneratedQuery<T> {
+    requested_size: Option<usize>,
+    effective_size: Option<usize>,
+    n_shared: Option<usize>,
+    spec: Option<(usize, String)>,
+    source: String,
+    triples: Vec<T>,
+
+    /// injected triples that are not contained in the main dataset, written after `triples`
+    misses: Vec<[TripleElementId; 3]>,
+}
+
+impl<T: GeneratedTriple> GeneratedQuery<T> {
+    fn info(&self) -> QueryInfo<'_> {
+        let mut source_files = Vec::new();
+
+        for triple in &self.triples {
+            if let Some(file) = triple.source_file() {
+                if source_files.last() != Some(&file) && !source_files.contains(&file) {
+                    source_files.push(file);
+                }
+            }
+        }
+
+        QueryInfo {
+            size: self.triples.len() + self.misses.len(),
+            requested_size: self.requested_size,
+            effective_size: self.effective_size,
+            n_shared: self.n_shared,
+            n_misses: self.misses.len(),
+            n_matched: None,
+            spec: self.spec.as_ref().map(|(ix, description)| (*ix, description.as_str())),
+            source: &self.source,
+            source_files,
+        }
+    }
+}
+
+/// Describes query spec `ix` in the index of the output
+fn spec_source(ix: usize, QuerySpec { description, .. }: &QuerySpec) -> String {
+    format!("spec {ix} ({description})")
+}
+
+/// Splits the (ordered) generators into consecutive batches that are generated in parallel and then written.
+///
+/// A batch holds at most [`MAX_QUERIES_PER_BATCH`] queries and, judged by `estimated_size`,
+/// at most [`MAX_TRIPLES_IN_FLIGHT`] triples, unless it consists of a single larger query.
+fn into_batches<G, E>(generators: impl IntoIterator<Item = G>, estimated_size: E) -> impl Iterator<Item = Vec<G>>
+where
+    E: Fn(&G) -> usize,
+{
+    let mut generators = generators.into_iter().peekable();
+
+    std::iter::from_fn(move || {
+        let mut batch = Vec::new();
+        let mut n_triples = 0;
+
+        while let Some(generator) = generators.next_if(|generator| {
+            batch.is_empty()
+                || (batch.len() < MAX_QUERIES_PER_BATCH
+                    && n_triples + estimated_size(generator) <= MAX_TRIPLES_IN_FLIGHT)
+        }) {
+            n_triples += estimated_size(&generator);
+            batch.push(generator);
+        }
+
+        (!batch.is_empty()).then_some(batch)
+    })
+}
+
+/// Generates the queries described by `query_specs` with triples from `triple_generator_factory`.
+///
+/// `rng` is only used to shuffle the queries for [`OutputOrder::Randomized`].
+#[allow(clippy::too_many_arguments)]
+pub fn generate_queries<Q, F, I, T, R>(
+    output: &QueryOutput,
+    query_specs: Q,
+    decompressor: &RdfTripleDecompressor,
+    mut triple_generator_factory: F,
+    order: OutputOrder,
+    update_kind: UpdateKind,
+    QueryAdjustments { mut misses, overlap, mut simulation, max_bytes }: QueryAdjustments,
+    rng: &mut R,
+) -> std::io::Result<()>
+where
+    Q: IntoIterator<Item = QuerySpec>,
+    F: FnMut(usize) -> I,
+    I: Iterator<Item = T> + Send,
+    T: GeneratedTriple + Clone + Send + Sync,
+    R: Rng,
+{
+    let query_specs: Vec<_> = query_specs.into_iter().collect();
+
+    let mut overlap = overlap.map(QueryOverlap::new);
+    let max_body_len = max_bytes.map(|max_bytes| update_kind.max_body_len(max_bytes));
+    let mut n_byte_limited = 0;
+
+    let mut writer = QueryWriter::create(output)?;
+    let mut n_total_triples = 0;
+    let mut n_effective_triples = 0;
+
+    for batch in into_batches(ordered_queries(&query_specs, order, rng), |(n_triples, _)| *n_triples) {
+        let batch: Vec<_> = batch
+            .into_iter()
+            .map(|(n_triples, spec)| (n_triples, (spec, triple_generator_factory(n_triples))))
+            .collect();
+
+        let (mut queries, byte_limited): (Vec<_>, Vec<_>) = batch
+            .into_par_iter()
+            .map(|(n_triples, (spec, triple_generator))| {
+                let mut byte_limited = false;
+                let triples: Vec<_> = match max_bytes.zip(max_body_len) {
+                    Some((max_bytes, max_body_len)) => {
+                        // stop at the first triple that does not fit, generators that keep track of their
+                        // position (i.e. fixed size changesets) stop before it themselves
+                        let mut triples = Vec::new();
+                        let mut body_len = 0;
+
+                        for triple in triple_generator {
+                            let len = rendered_triple_len(decompressor, triple.borrow());
+
+                            if len > max_body_len {
+                                return Err(std::io::Error::new(
+                                    std::io::ErrorKind::InvalidInput,
+                                    format!("a triple of {len} bytes does not fit into a query of {max_bytes} bytes"),
+                                ));
+                            }
+
+                            if body_len + len > max_body_len {
+                                byte_limited = true;
+                                break;
+                            }
+
+                            body_len += len;
+                            triples.push(triple);
+                        }
+
+                        byte_limited |= !triples.is_empty() && triples.len() < n_triples;
+                        triples
+                    },
+                    None => triple_generator.collect(),
+                };
+
+                if triples.len() != n_triples && !byte_limited {
+                    println!(
+                        "Warning: requested query size {n_triples} cannot be fulfilled closest available size is {}",
+                        triples.len()
+                    );
+                }
+
+                let query = GeneratedQuery {
+                    requested_size: Some(n_triples),
+                    effective_size: None,
+                    n_shared: None,
+                    spec: Some((spec, query_specs[spec].description.clone())),
+                    source: spec_source(spec, &query_specs[spec]),
+                    triples,
+                    misses: Vec::new(),
+                };
+
+                Ok((query, byte_limited))
+            })
+            .collect::<std::io::Result<Vec<_>>>()?
+            .into_iter()
+            .unzip();
+
+        n_byte_limited += byte_limited.into_iter().filter(|&byte_limited| byte_limited).count();
+
+        if let Some(misses) = &mut misses {
+            for query in &mut queries {
+                query.misses = misses.apply(&mut query.triples);
+            }
+        }
+
+        if let Some(overlap) = &mut overlap {
+            for query in &mut queries {
+                query.n_shared = Some(overlap.apply(&mut query.triples));
+            }
+        }
+
+        if let Some(simulation) = &mut simulation {
+            for query in &mut queries {
+                n_total_triples += query.triples.len();
+
+                let effective_size = simulation.apply(update_kind, &mut query.triples);
+                query.effective_size = Some(effective_size);
+
+                n_effective_triples += effective_size;
+            }
+        }
+
+        write_update_queries(&mut writer, decompressor, update_kind, &queries)?;
+    }
+
+    if let Some(max_bytes) = max_bytes {
+        println!("{n_byte_limited} queries were cut short to stay within {max_bytes} bytes");
+    }
+
+    if let Some(simulation) = &simulation {
+        println!(
+            "{} of {n_total_triples} generated triples would not have changed the store when their query runs{}",
+            n_total_triples - n_effective_triples,
+            if simulation.is_filtering() { " and were dropped" } else { "" },
+        );
+    }
+
+    writer.finish()
+}
+
+/// Generates one query per generator yielded by `triple_generator_factory`,
+/// each generator comes with a description of its source for the index of the output
+pub fn generate_linear_no_size_hint<F, I, T>(
+    output: &QueryOutput,
+    decompressor: &RdfTripleDecompressor,
+    triple_generator_factory: F,
+    update_kind: UpdateKind,
+) -> std::io::Result<()>
+where
+    F: IntoIterator<Item = (String, I)>,
+    I: Iterator<Item = T> + Send,
+    T: GeneratedTriple + Send + Sync,
+{
+    let mut writer = QueryWriter::create(output)?;
+
+    for batch in into_batches(triple_generator_factory, |(_, triple_generator)| triple_generator.size_hint().0) {
+        let queries: Vec<_> = batch
+            .into_par_iter()
+            .map(|(source, triple_generator)| GeneratedQuery {
+                requested_size: None,
+                effective_size: None,
+                n_shared: None,
+                spec: None,
+                source,
+                triples: triple_generator.collect(),
+                misses: Vec::new(),
+            })
+            .collect();
+
+        write_update_queries(&mut writer, decompressor, update_kind, &queries)?;
+    }
+
+    writer.finish()
+}
+
+/// Decompresses the triples into the body of a DATA block, i.e. `s p o . ` for every triple
+fn render_triples<T: Borrow<[u64; 3]>>(decompressor: &RdfTripleDecompressor, triples: &[T]) -> String {
+    let mut body = String::new();
+
+    for triple in triples {
+        let [s, p, o] = decompressor
+            .decompress_rdf_triple(triple.borrow())
+            .expect("to use same compressor as used for compression");
+
+        for element in [s, " ", p, " ", o, " . "] {
+            body.push_str(element);
+        }
+    }
+
+    body
+}
+
+/// Number of bytes the triple takes up in the body of a DATA block, see [`render_triples`]
+pub fn rendered_triple_len(decompressor: &RdfTripleDecompressor, triple: &[TripleElementId; 3]) -> usize {
+    let [s, p, o] = decompressor
+        .decompress_rdf_triple(triple)
+        .expect("to use same compressor as used for compression");
+
+    s.len() + p.len() + o.len() + " ".len() * 2 + " . ".len()
+}
+
+/// Renders and writes the triples in windows of [`MAX_TRIPLES_IN_FLIGHT`] triples
+fn write_windowed<T>(writer: &mut dyn Write, decompressor: &RdfTripleDecompressor, triples: &[T]) -> std::io::Result<()>
+where
+    T: Borrow<[u64; 3]> + Sync,
+{
+    for window in triples.chunks(MAX_TRIPLES_IN_FLIGHT) {
+        let pieces: Vec<_> = window
+            .par_chunks(DECOMPRESSION_CHUNK_SIZE)
+            .map(|chunk| render_triples(decompressor, chunk))
+            .collect();
+
+        for piece in pieces {
+            writer.write_all(piece.as_bytes())?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Writes the queries in order. Small batches are rendered in one parallel pass,
+/// otherwise every query is rendered and written in windows of [`MAX_TRIPLES_IN_FLIGHT`] triples,
+/// so that at no point more than that many decompressed triples are held in memory.
+fn write_update_queries<T>(
+    writer: &mut QueryWriter,
+    decompressor: &RdfTripleDecompressor,
+    update_kind: UpdateKind,
+    queries: &[GeneratedQuery<T>],
+) -> std::io::Result<()>
+where
+    T: GeneratedTriple + Sync,
+{
+    let serialization = &writer.output().serialization;
+
+    if !serialization.is_plain() {
+        for query in queries {
+            let triples = query.triples.iter().map(|triple| *triple.borrow()).chain(query.misses.iter().copied());
+
+            let triples: Vec<_> = triples.collect();
+
+            for operation in update_kind.operations() {
+                writer.write_query(query.info(), |writer| {
+                    serialization.write_update(writer, decompressor, operation, triples.clone())
+                })?;
+            }
+        }
+
+        return Ok(());
+    }
+
+    let n_triples: usize = queries.iter().map(|query| query.triples.len() + query.misses.len()).sum();
+
+    if n_triples <= MAX_TRIPLES_IN_FLIGHT {
+        let bodies: Vec<_> = queries
+            .par_iter()
+            .map(|query| render_triples(decompressor, &query.triples) + &render_triples(decompressor, &query.misses))
+            .collect();
+
+        for (query, body) in queries.iter().zip(bodies) {
+            for operation in update_kind.operations() {
+                writer.write_query(query.info(), |writer| writeln!(writer, "{operation} {{ {body}}}"))?;
+            }
+        }
+
+        return Ok(());
+    }
+
+    for query in queries {
+        for operation in update_kind.operations() {
+            writer.write_query(query.info(), |writer| {
+                write!(writer, "{operation} {{ ")?;
+                write_windowed(writer, decompressor, &query.triples)?;
+                write_windowed(writer, decompressor, &query.misses)?;
+                writeln!(writer, "}}")
+            })?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Generates pattern based update queries, each consisting of as many pattern operations as its requested size
+/// separated by `;`. `pattern_generator_factory` yields the triples matched by each pattern,
+/// i.e. runs of the sorted main dataset sharing the same subject (and predicate).
+pub fn generate_pattern_queries<'a, Q, F, I, R>(
+    output: &QueryOutput,
+    query_specs: Q,
+    decompressor: &RdfTripleDecompressor,
+    mut pattern_generator_factory: F,
+    PatternSpec { kind, operation }: PatternSpec,
+    order: OutputOrder,
+    rng: &mut R,
+) -> std::io::Result<()>
+where
+    Q: IntoIterator<Item = QuerySpec>,
+    F: FnMut(usize) -> I,
+    I: Iterator<Item = &'a [[TripleElementId; 3]]> + Send,
+    R: Rng,
+{
+    let query_specs: Vec<_> = query_specs.into_iter().collect();
+    let serialization = &output.serialization;
+
+    let mut writer = QueryWriter::create(output)?;
+    let mut n_written_queries = 0;
+    let mut total_matched_triples = 0;
+
+    for batch in into_batches(ordered_queries(&query_specs, order, rng), |(n_patterns, _)| *n_patterns) {
+        let batch: Vec<_> = batch
+            .into_iter()
+            .map(|(n_patterns, spec)| (n_patterns, (spec, pattern_generator_factory(n_patterns))))
+            .collect();
+
+        let queries: Vec<_> = batch
+            .into_par_iter()
+            .map(|(n_patterns, (spec, pattern_generator))| {
+                let mut n_matched_triples = 0;
+                let mut used_prefixes = BTreeSet::new();
+
+                let operations: Vec<_> = pattern_generator
+                    .map(|matched_triples| {
+                        n_matched_triples += matched_triples.len();
+
+                        let [s, p, _] = decompressor
+                            .decompress_rdf_triple(&matched_triples[0])
+                            .expect("to use same compressor as used for compression");
+
+                        let render = |term: &str, is_predicate| {
+                            let mut out = Vec::new();
+                            serialization
+                                .write_term(&mut out, term, is_predicate)
+                                .expect("writing to memory to succeed");
+                            String::from_utf8(out).expect("terms to be valid utf-8")
+                        };
+
+                        let pattern = match kind {
+                            PatternKind::Subject => {
+                                used_prefixes.extend(serialization.used_prefixes([(s, false)]));
+                                format!("{} ?p ?o", render(s, false))
+                            },
+                            PatternKind::SubjectPredicate => {
+                                used_prefixes.extend(serialization.used_prefixes([(s, false), (p, true)]));
+                                format!("{} {} ?o", render(s, false), render(p, true))
+                            },
+                        };
+
+                        match operation {
+                            PatternOperation::DeleteWhere => format!("DELETE WHERE {{ {pattern} }}"),
+                            PatternOperation::DeleteInsertWhere => {
+                                format!("DELETE {{ {pattern} }} INSERT {{ {pattern} }} WHERE {{ {pattern} }}")
+                            },
+                        }
+                    })
+                    .collect();
+
+                if operations.len() != n_patterns {
+                    println!(
+                        "Warning: requested {n_patterns} patterns per query cannot be fulfilled closest available number is {}",
+                        operations.len()
+                    );
+                }
+
+                (n_patterns, spec, operations, used_prefixes, n_matched_triples)
+            })
+            .collect();
+
+        for (n_patterns, spec, operations, used_prefixes, n_matched_triples) in queries {
+            println!(
+                "query {n_written_queries}: {} patterns matching {n_matched_triples} triples of the main dataset",
+                operations.len()
+            );
+
+            let info = QueryInfo {
+                size: operations.len(),
+                requested_size: Some(n_patterns),
+                effective_size: None,
+                n_shared: None,
+                n_misses: 0,
+                n_matched: Some(n_matched_triples),
+                spec: Some((spec, query_specs[spec].description.as_str())),
+                source: &spec_source(spec, &query_specs[spec]),
+                source_files: Vec::new(),
+            };
+
+            writer.write_query(info, |writer| {
+                serialization.write_prologue(writer, &used_prefixes)?;
+                writeln!(writer, "{}", operations.join(if serialization.pretty { " ;\n" } else { OPERATION_SEPARATOR }))
+            })?;
+
+            n_written_queries += 1;
+            total_matched_triples += n_matched_triples;
+        }
+    }
+
+    println!("all queries together match {total_matched_triples} triples of the main dataset");
+
+    writer.finish()
+}
@@ -0,0 +1,354 @@
+//! Layouts in which generated queries are written to disk, and the per-query metadata written next to them
+
+use super::serialize::{QuerySerialization, SerializationOpts};
+use crate::{rdf::triple_compressor::decompressor::RdfTripleDecompressor, util::with_file_name_suffix};
+use clap::{ArgEnum, Args};
+use serde::Deserialize;
+use serde_json::json;
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+};
+
+/// Name of the index file inside of the output directory of all layouts except [`OutputLayout::SingleFile`]
+pub const INDEX_FILE_NAME: &str = "index.tsv";
+
+pub const QUERY_FILE_EXTENSION: &str = "rq";
+
+/// Suffix of the JSON Lines file next to the query output holding one metadata record per query
+pub const METADATA_FILE_SUFFIX: &str = ".queries.jsonl";
+
+const INDEX_HEADER: &str = "query\tfile\tline\tsize\tsource";
+
+#[derive(Copy, Clone, Default, ArgEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OutputLayout {
+    /// all queries in the file query-out, one query per line
+    #[default]
+    SingleFile,
+
+    /// query-out is a directory with one numbered file per query
+    Directory,
+
+    /// query-out is a directory with a fixed number of files, queries are distributed round-robin
+    Shards,
+
+    /// query-out is a directory with one file per query spec
+    PerSpec,
+}
+
+/// Where and in which layout the generated queries are written to
+#[derive(Clone)]
+pub struct QueryOutput {
+    pub path: PathBuf,
+
+    /// append to the existing output instead of overwriting it
+    pub append: bool,
+
+    pub layout: OutputLayout,
+
+    /// number of files for [`OutputLayout::Shards`]
+    pub n_shards: usize,
+
+    pub serialization: QuerySerialization,
+}
+
+/// Command line options from which the [`QueryOutput`] is built
+#[derive(Args)]
+pub struct OutputOpts {
+    /// File (or directory, depending on the layout) to write the queries to.
+    /// Metadata about every query is written to <QUERY_OUT>.queries.jsonl
+    #[clap(short = 'o', long)]
+    pub query_out: PathBuf,
+
+    /// Append to query-out instead of overwriting it
+    #[clap(short, long, action)]
+    pub append: bool,
+
+    /// How the queries are laid out on disk. For all layouts except single-file query-out is a directory
+    /// that also contains an index file (index.tsv) listing the file, line, size and source of every query
+    #[clap(arg_enum, short = 'L', long, default_value_t = OutputLayout::SingleFile)]
+    pub layout: OutputLayout,
+
+    /// Number of files for the shards layout
+    #[clap(long, default_value_t = 1, value_parser = clap::value_parser!(u64).range(1..))]
+    pub shards: u64,
+
+    #[clap(flatten)]
+    pub serialization: SerializationOpts,
+}
+
+impl OutputOpts {
+    pub fn into_query_output(self, decompressor: &RdfTripleDecompressor) -> QueryOutput {
+        let OutputOpts { query_out, append, layout, shards, serialization } = self;
+
+        QueryOutput {
+            path: query_out,
+            append,
+            layout,
+            n_shards: shards as usize,
+            serialization: serialization.into_serialization(decompressor),
+        }
+    }
+}
+
+impl QueryOutput {
+    /// Path of the JSON Lines file with the per-query metadata
+    pub fn metadata_path(&self) -> PathBuf {
+        with_file_name_suffix(&self.path, METADATA_FILE_SUFFIX)
+    }
+}
+
+/// Describes a single query for the index and metadata files
+pub struct QueryInfo<'s> {
+    /// the number of triples (or patterns) of the query
+    pub size: usize,
+
+    /// the number of triples (or patterns) that were requested, if a size was requested
+    pub requested_size: Option<usize>,
+
+    /// the number of triples that change the store when the query runs, if the store state is tracked
+    pub effective_size: Option<usize>,
+
+    /// the number of triples taken from earlier queries, if queries are generated with overlap
+    pub n_shared: Option<usize>,
+
+    /// the number of injected triples that are not contained in the main dataset
+    pub n_misses: usize,
+
+    /// the number of triples of the main dataset matched by the patterns, if the query is a pattern query
+    pub n_matched: Option<usize>,
+
+    /// the index and description of the query spec the query was generated for
+    pub spec: Option<(usize, &'s str)>,
+
+    /// human readable description of where the query came from, e.g. the query spec or the replicated dataset
+    pub source: &'s str,
+
+    /// the files (e.g. changesets) the triples of the query were taken from, if not from the main dataset,
+    /// in the order of their first use
+    pub source_files: Vec<&'s Path>,
+}
+
+/// Counts the bytes and lines written through it
+struct CountingWriter<W> {
+    inner: W,
+    n_bytes: usize,
+    n_lines: usize,
+}
+
+impl<W: Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+
+        self.n_bytes += n;
+        self.n_lines += buf[..n].iter().filter(|&&b| b == b'\n').count();
+
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+fn count_lines(file: &File) -> io::Result<usize> {
+    let mut n_lines = 0;
+
+    for byte in BufReader::new(file).bytes() {
+        if byte? == b'\n' {
+            n_lines += 1;
+        }
+    }
+
+    Ok(n_lines)
+}
+
+fn open_output_file<P: AsRef<Path>>(path: P, append: bool) -> io::Result<File> {
+    File::options()
+        .read(true)
+        .append(append)
+        .truncate(!append)
+        .create(true)
+        .write(true)
+        .open(path)
+}
+
+/// Writes the queries in the layout of a [`QueryOutput`] and keeps the index and metadata files up to date
+pub struct QueryWriter<'o> {
+    output: &'o QueryOutput,
+    files: HashMap<String, CountingWriter<BufWriter<File>>>,
+    index: Option<BufWriter<File>>,
+    metadata: BufWriter<File>,
+    next_query: usize,
+
+    /// the sizes in bytes of the queries written by this writer
+    query_bytes: Vec<usize>,
+}
+
+impl<'o> QueryWriter<'o> {
+    pub fn create(output: &'o QueryOutput) -> io::Result<Self> {
+        assert!(output.n_shards > 0, "at least one shard");
+
+        let metadata_path = output.metadata_path();
+
+        // continue the numbering of the previous runs
+        let next_query = if output.append && metadata_path.exists() {
+            BufReader::new(File::open(&metadata_path)?).lines().count()
+        } else {
+            0
+        };
+
+        let metadata = BufWriter::new(open_output_file(metadata_path, output.append)?);
+
+        let mut writer =
+            Self { output, files: HashMap::new(), index: None, metadata, next_query, query_bytes: Vec::new() };
+
+        if let OutputLayout::SingleFile = output.layout {
+            return Ok(writer);
+        }
+
+        std::fs::create_dir_all(&output.path)?;
+
+        let index = open_output_file(output.path.join(INDEX_FILE_NAME), output.append)?;
+        let is_empty = index.metadata()?.len() == 0;
+        let mut index = BufWriter::new(index);
+
+        if is_empty {
+            writeln!(index, "{INDEX_HEADER}")?;
+        }
+
+        writer.index = Some(index);
+        Ok(writer)
+    }
+
+    pub fn output(&self) -> &'o QueryOutput {
+        self.output
+    }
+
+    fn file_name(&self, info: &QueryInfo) -> String {
+        match self.output.layout {
+            OutputLayout::SingleFile => String::new(),
+            OutputLayout::Directory => format!("{:08}.{QUERY_FILE_EXTENSION}", self.next_query),
+            OutputLayout::Shards => {
+                format!("shard-{}.{QUERY_FILE_EXTENSION}", self.next_query % self.output.n_shards)
+            },
+            OutputLayout::PerSpec => {
+                format!("spec-{}.{QUERY_FILE_EXTENSION}", info.spec.map_or(0, |(ix, _)| ix))
+            },
+        }
+    }
+
+    fn file_path(&self, file_name: &str) -> PathBuf {
+        match self.output.layout {
+            OutputLayout::SingleFile => self.output.path.clone(),
+            _ => self.output.path.join(file_name),
+        }
+    }
+
+    fn open_file(&self, file_name: &str) -> io::Result<CountingWriter<BufWriter<File>>> {
+        let f = open_output_file(self.file_path(file_name), self.output.append)?;
+        let n_lines = if self.output.append { count_lines(&f)? } else { 0 };
+
+        Ok(CountingWriter { inner: BufWriter::new(f), n_bytes: 0, n_lines })
+    }
+
+    /// Writes the next query with `write_query` and records it in the index and metadata files
+    pub fn write_query<F>(&mut self, info: QueryInfo, write_query: F) -> io::Result<()>
+    where
+        F: FnOnce(&mut dyn Write) -> io::Result<()>,
+    {
+        let file_name = self.file_name(&info);
+
+        if !self.files.contains_key(&file_name) {
+            if let OutputLayout::Directory = self.output.layout {
+                // every query gets its own file, so there is no need to keep the previous one open
+                for (_, mut file) in self.files.drain() {
+                    file.flush()?;
+                }
+            }
+
+            let file = self.open_file(&file_name)?;
+            self.files.insert(file_name.clone(), file);
+        }
+
+        let file = self.files.get_mut(&file_name).expect("file to be opened above");
+
+        let line = file.n_lines + 1;
+        let bytes_before = file.n_bytes;
+
+        write_query(file)?;
+
+        let n_bytes = file.n_bytes - bytes_before;
+
+        if let Some(index) = &mut self.index {
+            writeln!(index, "{}\t{file_name}\t{line}\t{}\t{}", self.next_query, info.size, info.source)?;
+        }
+
+        let source_files: Vec<_> = info.source_files.iter().map(|path| path.to_string_lossy()).collect();
+
+        let record = json!({
+            "query": self.next_query,
+            "file": self.file_path(&file_name).to_string_lossy(),
+            "line": line,
+            "size": info.size,
+            "requested_size": info.requested_size,
+            "effective_size": info.effective_size,
+            "shared": info.n_shared,
+            "misses": info.n_misses,
+            "matched": info.n_matched,
+            "spec": info.spec.map(|(ix, _)| ix),
+            "query_spec": info.spec.map(|(_, description)| description),
+            "source": info.source,
+            "source_files": source_files,
+            "n_source_files": source_files.len(),
+            "bytes": n_bytes,
+        });
+
+        writeln!(self.metadata, "{record}")?;
+
+        self.query_bytes.push(n_bytes);
+        self.next_query += 1;
+        Ok(())
+    }
+
+    /// Prints the distribution of the sizes in bytes of the written queries
+    fn print_byte_size_stats(&mut self) {
+        let sizes = &mut self.query_bytes;
+        if sizes.is_empty() {
+            return;
+        }
+
+        sizes.sort_unstable();
+
+        let percentile = |p: usize| sizes[(sizes.len() - 1) * p / 100];
+        let total: usize = sizes.iter().sum();
+
+        println!(
+            "query sizes in bytes: min {}, median {}, p90 {}, p99 {}, max {}, mean {:.1}, total {total}",
+            sizes[0],
+            percentile(50),
+            percentile(90),
+            percentile(99),
+            sizes[sizes.len() - 1],
+            total as f64 / sizes.len() as f64,
+        );
+    }
+
+    /// Flushes all query files, the index file and the metadata file and prints statistics about the written queries
+    pub fn finish(mut self) -> io::Result<()> {
+        self.print_byte_size_stats();
+
+        for (_, mut file) in self.files.drain() {
+            file.flush()?;
+        }
+
+        if let Some(index) = &mut self.index {
+            index.flush()?;
+        }
+
+        self.metadata.flush()
+    }
+}
@@ -0,0 +1,85 @@
+//! Controlled overlap between consecutive queries: a fixed fraction of each query's triples
+//! is replaced by triples of earlier queries
+
+use clap::ArgEnum;
+use rand::rngs::SmallRng;
+use serde::Deserialize;
+
+#[derive(Copy, Clone, Default, ArgEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OverlapTarget {
+    /// shared triples are taken from the directly preceding query
+    #[default]
+    Preceding,
+
+    /// shared triples are taken from all earlier queries
+    AnyEarlier,
+}
+
+/// How strongly and with which earlier queries the generated queries overlap
+pub struct Overlap {
+    /// fraction of the triples of every query that are taken from earlier queries
+    pub ratio: f64,
+
+    pub target: OverlapTarget,
+
+    /// used to choose the shared triples
+    pub rng: SmallRng,
+}
+
+/// Replaces a fixed fraction of the triples of every query with triples of earlier queries (in output order).
+///
+/// The ratio is only exact if the generator itself does not produce overlapping queries,
+/// e.g. the distinct randomized generator.
+pub struct QueryOverlap<T> {
+    ratio: f64,
+    target: OverlapTarget,
+
+    /// for [`OverlapTarget::Preceding`] the preceding query, otherwise the triples of all earlier queries
+    /// that were not themselves taken from an earlier query
+    earlier: Vec<T>,
+
+    rng: SmallRng,
+}
+
+impl<T: Clone> QueryOverlap<T> {
+    pub fn new(Overlap { ratio, target, rng }: Overlap) -> Self {
+        assert!((0.0..=1.0).contains(&ratio), "overlap ratio must be between 0 and 1");
+        Self { ratio, target, earlier: Vec::new(), rng }
+    }
+
+    /// Replaces the tail of `triples` with randomly chosen earlier triples and returns
+    /// the number of triples now shared with earlier queries. Fewer triples are shared
+    /// if there are not enough earlier triples, e.g. for the first query.
+    pub fn apply(&mut self, triples: &mut Vec<T>) -> usize {
+        let n_wanted = (triples.len() as f64 * self.ratio).round() as usize;
+        let n_shared = n_wanted.min(self.earlier.len());
+
+        if n_shared < n_wanted && !self.earlier.is_empty() {
+            println!(
+                "Warning: requested overlap of {n_wanted} triples cannot be fulfilled, only {} earlier triples available",
+                self.earlier.len()
+            );
+        }
+
+        let n_fresh = triples.len() - n_shared;
+        triples.truncate(n_fresh);
+
+        let shared: Vec<_> = rand::seq::index::sample(&mut self.rng, self.earlier.len(), n_shared)
+            .into_iter()
+            .map(|ix| self.earlier[ix].clone())
+            .collect();
+
+        match self.target {
+            OverlapTarget::Preceding => {
+                self.earlier.clear();
+                self.earlier.extend_from_slice(triples);
+                self.earlier.extend_from_slice(&shared);
+            },
+            OverlapTarget::AnyEarlier => self.earlier.extend_from_slice(triples),
+        }
+
+        triples.extend(shared);
+        n_shared
+    }
+}
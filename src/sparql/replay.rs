@@ -0,0 +1,108 @@
+//! Chronological replay of the changesets of a live system, each consisting of removed and added triples
+
+use super::output::{QueryInfo, QueryOutput, QueryWriter};
+use crate::{
+    rdf::triple_compressor::{decompressor::RdfTripleDecompressor, CompressedRdfTriples, TripleElementId},
+    util::ChangesetPair,
+};
+use clap::ArgEnum;
+use serde::Deserialize;
+use std::path::Path;
+
+#[derive(Copy, Clone, Default, ArgEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ReplayMode {
+    /// a DELETE DATA query with the removed triples followed by an INSERT DATA query with the added triples,
+    /// queries without triples are left out
+    #[default]
+    Separate,
+
+    /// a single DELETE { .. } INSERT { .. } WHERE { } query per changeset
+    Combined,
+}
+
+fn load(path: &Option<impl AsRef<Path>>) -> std::io::Result<Option<CompressedRdfTriples>> {
+    path.as_ref().map(|path| unsafe { CompressedRdfTriples::load(path) }).transpose()
+}
+
+fn triples_or_empty(triples: &Option<CompressedRdfTriples>) -> &[[TripleElementId; 3]] {
+    match triples {
+        Some(triples) => triples,
+        None => &[],
+    }
+}
+
+fn replay_info<'s>(source: &'s str, size: usize, source_files: Vec<&'s Path>) -> QueryInfo<'s> {
+    QueryInfo {
+        size,
+        requested_size: None,
+        effective_size: None,
+        n_shared: None,
+        n_misses: 0,
+        n_matched: None,
+        spec: None,
+        source,
+        source_files,
+    }
+}
+
+/// Writes the queries of the changesets in the given order, removed triples are always applied before added ones
+pub fn generate_replay_queries(
+    output: &QueryOutput,
+    decompressor: &RdfTripleDecompressor,
+    changesets: &[ChangesetPair],
+    mode: ReplayMode,
+) -> std::io::Result<()> {
+    let serialization = &output.serialization;
+    let mut writer = QueryWriter::create(output)?;
+    let mut n_removed_total = 0;
+    let mut n_added_total = 0;
+
+    for ChangesetPair { name, removed: removed_path, added: added_path } in changesets {
+        let removed = load(removed_path)?;
+        let added = load(added_path)?;
+
+        let (removed, added) = (triples_or_empty(&removed), triples_or_empty(&added));
+
+        n_removed_total += removed.len();
+        n_added_total += added.len();
+
+        let source = name.display().to_string();
+
+        match mode {
+            ReplayMode::Separate => {
+                for (operation, triples, path) in
+                    [("DELETE DATA", removed, removed_path), ("INSERT DATA", added, added_path)]
+                {
+                    if triples.is_empty() {
+                        continue;
+                    }
+
+                    let source_files = path.iter().map(|path| path.as_path()).collect();
+
+                    writer.write_query(replay_info(&source, triples.len(), source_files), |writer| {
+                        serialization.write_update(writer, decompressor, operation, triples.to_vec())
+                    })?;
+                }
+            },
+            ReplayMode::Combined => {
+                if removed.is_empty() && added.is_empty() {
+                    continue;
+                }
+
+                let source_files = removed_path.iter().chain(added_path).map(|path| path.as_path()).collect();
+
+                writer.write_query(replay_info(&source, removed.len() + added.len(), source_files), |writer| {
+                    serialization.write_delete_insert(writer, decompressor, removed.to_vec(), added.to_vec())
+                })?;
+            },
+        }
+    }
+
+    println!(
+        "replayed {} changesets removing {n_removed_total} and adding {n_added_total} triples",
+        changesets.len()
+    );
+
+    writer.finish()
+}
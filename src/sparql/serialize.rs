@@ -0,0 +1,259 @@
+//! Serialization of the triples of update queries with prefixed names and optional pretty printing
+
+use super::{DECOMPRESSION_CHUNK_SIZE, MAX_TRIPLES_IN_FLIGHT};
+use crate::rdf::{
+    format::{parse_term, write_turtle_predicate, write_turtle_term, RDF_TYPE},
+    prefix::{PrefixDecl, PrefixMap},
+    term::NTriplesTerm,
+    triple_compressor::{decompressor::RdfTripleDecompressor, TripleElementId},
+};
+use clap::Args;
+use rayon::{
+    iter::{IndexedParallelIterator, ParallelIterator},
+    slice::ParallelSlice,
+};
+use std::{collections::BTreeSet, io::Write};
+
+/// Command line options from which the [`QuerySerialization`] is built
+#[derive(Args)]
+pub struct SerializationOpts {
+    /// Prefix declarations of the form <PREFIX>=<NAMESPACE_IRI> used to abbreviate IRIs in the queries.
+    /// Every query declares the prefixes it uses
+    #[clap(long = "prefix", value_parser)]
+    pub prefixes: Vec<PrefixDecl>,
+
+    /// Additionally declare prefixes for the N most frequent IRI namespaces in the compressor state
+    #[clap(long, default_value_t = 0)]
+    pub learn_prefixes: usize,
+
+    /// Write one triple per line, grouped by subject. Queries then span several lines,
+    /// which fits the directory layout best
+    #[clap(long, action)]
+    pub pretty: bool,
+}
+
+impl SerializationOpts {
+    pub fn into_serialization(self, decompressor: &RdfTripleDecompressor) -> QuerySerialization {
+        let SerializationOpts { prefixes, learn_prefixes, pretty } = self;
+        let prefixes =
+            PrefixMap::from_declarations(prefixes, decompressor.terms().map(|(_, term)| term), learn_prefixes);
+
+        QuerySerialization { prefixes, pretty }
+    }
+}
+
+/// How the triples of a query are written
+#[derive(Clone, Default)]
+pub struct QuerySerialization {
+    /// prefixes used to abbreviate IRIs, every query declares the prefixes it uses
+    pub prefixes: PrefixMap,
+
+    /// one triple per line, grouped by subject with `;` and `,`
+    pub pretty: bool,
+}
+
+impl QuerySerialization {
+    /// Whether queries are written with full IRIs on a single line, i.e. as without a serialization
+    pub fn is_plain(&self) -> bool {
+        self.prefixes.is_empty() && !self.pretty
+    }
+
+    /// The prefix names used by the given terms (in n-triples syntax),
+    /// predicates are expected at every position where `rdf:type` would be written as `a`
+    pub fn used_prefixes<'a, 'm>(&'m self, terms: impl IntoIterator<Item = (&'a str, bool)>) -> BTreeSet<&'m str> {
+        let mut used = BTreeSet::new();
+
+        for (term, is_predicate) in terms {
+            match parse_term(term) {
+                NTriplesTerm::Iri(RDF_TYPE) if is_predicate => (),
+                term => self.prefixes.collect_used(&term, &mut used),
+            }
+        }
+
+        used
+    }
+
+    /// The prefix names used by the given triples, determined in parallel
+    fn used_prefixes_of_triples(
+        &self,
+        decompressor: &RdfTripleDecompressor,
+        triples: &[[TripleElementId; 3]],
+    ) -> BTreeSet<&str> {
+        if self.prefixes.is_empty() {
+            return BTreeSet::new();
+        }
+
+        triples
+            .par_chunks(DECOMPRESSION_CHUNK_SIZE)
+            .map(|chunk| {
+                self.used_prefixes(chunk.iter().flat_map(|triple| {
+                    let [s, p, o] = decompressor
+                        .decompress_rdf_triple(triple)
+                        .expect("to use same compressor as used for compression");
+                    [(s, false), (p, true), (o, false)]
+                }))
+            })
+            .reduce(BTreeSet::new, |mut a, b| {
+                a.extend(b);
+                a
+            })
+    }
+
+    /// Writes `PREFIX` declarations for the given prefix names
+    pub fn write_prologue(&self, writer: &mut dyn Write, used: &BTreeSet<&str>) -> std::io::Result<()> {
+        for prefix in used {
+            let namespace = self.prefixes.namespace(prefix).expect("used prefixes to be declared");
+
+            write!(writer, "PREFIX {prefix}: <{namespace}>")?;
+
+            if self.pretty {
+                writeln!(writer)?;
+            } else {
+                write!(writer, " ")?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Writes a single term (in n-triples syntax), as is for the plain serialization
+    pub fn write_term(&self, writer: &mut dyn Write, term: &str, is_predicate: bool) -> std::io::Result<()> {
+        let mut writer = writer;
+
+        if self.is_plain() {
+            writer.write_all(term.as_bytes())
+        } else if is_predicate {
+            write_turtle_predicate(&mut writer, term, &self.prefixes)
+        } else {
+            write_turtle_term(&mut writer, &parse_term(term), &self.prefixes)
+        }
+    }
+
+    /// Renders the triples, `prev` is the triple rendered directly before them, if any
+    fn render_chunk<'t>(
+        &self,
+        decompressor: &RdfTripleDecompressor,
+        triples: &'t [[TripleElementId; 3]],
+        mut prev: Option<&'t [TripleElementId; 3]>,
+    ) -> std::io::Result<Vec<u8>> {
+        let mut out = Vec::new();
+
+        for triple in triples {
+            let [s, p, o] = decompressor
+                .decompress_rdf_triple(triple)
+                .expect("to use same compressor as used for compression");
+
+            if !self.pretty {
+                self.write_term(&mut out, s, false)?;
+                write!(out, " ")?;
+                self.write_term(&mut out, p, true)?;
+                write!(out, " ")?;
+                self.write_term(&mut out, o, false)?;
+                write!(out, " . ")?;
+                continue;
+            }
+
+            match prev {
+                Some([prev_s, prev_p, _]) if *prev_s == triple[0] && *prev_p == triple[1] => {
+                    write!(out, " ,\n      ")?;
+                },
+                Some([prev_s, _, _]) if *prev_s == triple[0] => {
+                    write!(out, " ;\n    ")?;
+                    self.write_term(&mut out, p, true)?;
+                    write!(out, " ")?;
+                },
+                _ => {
+                    write!(out, "{}\n  ", if prev.is_some() { " ." } else { "" })?;
+                    self.write_term(&mut out, s, false)?;
+                    write!(out, " ")?;
+                    self.write_term(&mut out, p, true)?;
+                    write!(out, " ")?;
+                },
+            }
+
+            self.write_term(&mut out, o, false)?;
+            prev = Some(triple);
+        }
+
+        Ok(out)
+    }
+
+    /// Writes the triples as a block `{ .. }`, rendered in windows of [`MAX_TRIPLES_IN_FLIGHT`] triples.
+    /// For pretty printing the triples should be sorted, so that the triples of a subject are adjacent.
+    pub fn write_block(
+        &self,
+        writer: &mut dyn Write,
+        decompressor: &RdfTripleDecompressor,
+        triples: &[[TripleElementId; 3]],
+    ) -> std::io::Result<()> {
+        write!(writer, "{}", if self.pretty { "{" } else { "{ " })?;
+
+        for (window_ix, window) in triples.chunks(MAX_TRIPLES_IN_FLIGHT).enumerate() {
+            let window_start = window_ix * MAX_TRIPLES_IN_FLIGHT;
+
+            let pieces: Vec<_> = window
+                .par_chunks(DECOMPRESSION_CHUNK_SIZE)
+                .enumerate()
+                .map(|(chunk_ix, chunk)| {
+                    let start = window_start + chunk_ix * DECOMPRESSION_CHUNK_SIZE;
+                    self.render_chunk(decompressor, chunk, start.checked_sub(1).map(|prev| &triples[prev]))
+                })
+                .collect::<Result<_, _>>()?;
+
+            for piece in pieces {
+                writer.write_all(&piece)?;
+            }
+        }
+
+        if self.pretty && !triples.is_empty() {
+            write!(writer, " .\n}}")
+        } else {
+            write!(writer, "}}")
+        }
+    }
+
+    /// Writes a single update request with the `operation` (e.g. `DELETE DATA`) on all of the triples.
+    /// For pretty printing the triples are sorted first.
+    pub fn write_update(
+        &self,
+        writer: &mut dyn Write,
+        decompressor: &RdfTripleDecompressor,
+        operation: &str,
+        mut triples: Vec<[TripleElementId; 3]>,
+    ) -> std::io::Result<()> {
+        if self.pretty {
+            triples.sort_unstable();
+        }
+
+        let used = self.used_prefixes_of_triples(decompressor, &triples);
+        self.write_prologue(writer, &used)?;
+
+        write!(writer, "{operation} ")?;
+        self.write_block(writer, decompressor, &triples)?;
+        writeln!(writer)
+    }
+
+    /// Writes a single `DELETE { .. } INSERT { .. } WHERE { }` operation
+    pub fn write_delete_insert(
+        &self,
+        writer: &mut dyn Write,
+        decompressor: &RdfTripleDecompressor,
+        mut removed: Vec<[TripleElementId; 3]>,
+        mut added: Vec<[TripleElementId; 3]>,
+    ) -> std::io::Result<()> {
+        if self.pretty {
+            removed.sort_unstable();
+            added.sort_unstable();
+        }
+
+        let mut used = self.used_prefixes_of_triples(decompressor, &removed);
+        used.extend(self.used_prefixes_of_triples(decompressor, &added));
+
+        self.write_prologue(writer, &used)?;
+        write!(writer, "DELETE ")?;
+        self.write_block(writer, decompressor, &removed)?;
+        write!(writer, " INSERT ")?;
+        self.write_block(writer, decompressor, &added)?;
+        writeln!(writer, " WHERE {{ }}")
+    }
+}
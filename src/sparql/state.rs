@@ -0,0 +1,155 @@
+//! Simulation of the store state through the ordered query sequence, used to find (and drop)
+//! triples that no longer change the store when their query runs
+
+use super::UpdateKind;
+use crate::rdf::triple_compressor::{CompressedRdfTriples, TripleElementId};
+use clap::ArgEnum;
+use serde::Deserialize;
+use std::{borrow::Borrow, collections::HashMap, hash::BuildHasherDefault};
+
+#[derive(Copy, Clone, Default, ArgEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum StateTracking {
+    /// queries are written as generated
+    #[default]
+    Off,
+
+    /// queries are written as generated, the number of triples that actually change the store
+    /// is recorded as effective_size in the query metadata
+    Report,
+
+    /// triples that would not change the store when their query runs, e.g. because an earlier query already
+    /// deleted them, are dropped from the query
+    Filter,
+}
+
+/// The store state after the queries seen so far, starting with the main dataset
+pub struct StoreSimulation<'d> {
+    dataset: &'d CompressedRdfTriples,
+
+    /// triples whose presence differs from the main dataset or has been changed by a query
+    overlay: HashMap<[TripleElementId; 3], bool, BuildHasherDefault<ahash::AHasher>>,
+
+    filter: bool,
+}
+
+impl<'d> StoreSimulation<'d> {
+    /// Starts a simulation on top of `dataset`, or returns `None` for [`StateTracking::Off`]
+    pub fn new(dataset: &'d CompressedRdfTriples, tracking: StateTracking) -> Option<Self> {
+        let filter = match tracking {
+            StateTracking::Off => return None,
+            StateTracking::Report => false,
+            StateTracking::Filter => true,
+        };
+
+        Some(Self { dataset, overlay: HashMap::default(), filter })
+    }
+
+    /// Whether triples that do not change the store are removed from the queries
+    pub fn is_filtering(&self) -> bool {
+        self.filter
+    }
+
+    fn is_present(&self, triple: &[TripleElementId; 3]) -> bool {
+        match self.overlay.get(triple) {
+            Some(&present) => present,
+            None => self.dataset.contains(triple),
+        }
+    }
+
+    /// Runs the next query against the simulated store and returns its effective size,
+    /// i.e. the number of distinct triples that change the store.
+    /// In filter mode all other triples are removed from `triples`.
+    pub fn apply<T: Borrow<[TripleElementId; 3]>>(&mut self, update_kind: UpdateKind, triples: &mut Vec<T>) -> usize {
+        // a DELETE DATA only changes the store for present triples, an INSERT DATA only for absent ones.
+        // Updating the overlay while going through the query also takes care of duplicates within it
+        let deletes = !matches!(update_kind, UpdateKind::Insert);
+        let mut effective = Vec::new();
+
+        for triple in triples.iter() {
+            let triple = *triple.borrow();
+
+            if self.is_present(&triple) == deletes {
+                self.overlay.insert(triple, !deletes);
+                effective.push(triple);
+            }
+        }
+
+        if self.filter && effective.len() != triples.len() {
+            let mut remaining = effective.iter().peekable();
+            triples.retain(|triple| remaining.next_if(|&t| t == triple.borrow()).is_some());
+        }
+
+        if let UpdateKind::DeleteInsert = update_kind {
+            // the INSERT DATA restores exactly what the DELETE DATA removed
+            for triple in &effective {
+                self.overlay.insert(*triple, true);
+            }
+        }
+
+        effective.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn triple(n: TripleElementId) -> [TripleElementId; 3] {
+        [n, 0, n]
+    }
+
+    fn triples(ns: &[TripleElementId]) -> Vec<[TripleElementId; 3]> {
+        ns.iter().map(|&n| triple(n)).collect()
+    }
+
+    #[test]
+    fn tracking_off_has_no_simulation() {
+        let dataset = CompressedRdfTriples::from_triples("state-off", &triples(&[1]));
+        assert!(StoreSimulation::new(&dataset, StateTracking::Off).is_none());
+    }
+
+    #[test]
+    fn deletes_and_inserts_change_the_store() {
+        let dataset = CompressedRdfTriples::from_triples("state-report", &triples(&[1, 2]));
+        let mut simulation = StoreSimulation::new(&dataset, StateTracking::Report).unwrap();
+
+        // absent triples and duplicates within the query do not count, nothing is dropped when reporting
+        let mut query = triples(&[1, 3, 1]);
+        assert_eq!(simulation.apply(UpdateKind::Delete, &mut query), 1);
+        assert_eq!(query, triples(&[1, 3, 1]));
+
+        assert_eq!(simulation.apply(UpdateKind::Delete, &mut triples(&[1, 2])), 1);
+        assert_eq!(simulation.apply(UpdateKind::Insert, &mut triples(&[1, 3, 3])), 2);
+        assert_eq!(simulation.apply(UpdateKind::Delete, &mut triples(&[1, 2, 3])), 2);
+    }
+
+    #[test]
+    fn filtering_keeps_only_effective_triples() {
+        let dataset = CompressedRdfTriples::from_triples("state-filter", &triples(&[1, 2]));
+        let mut simulation = StoreSimulation::new(&dataset, StateTracking::Filter).unwrap();
+
+        let mut query = triples(&[3, 1, 2, 1]);
+        assert_eq!(simulation.apply(UpdateKind::Delete, &mut query), 2);
+        assert_eq!(query, triples(&[1, 2]));
+
+        let mut query = triples(&[2, 4]);
+        assert_eq!(simulation.apply(UpdateKind::Insert, &mut query), 2);
+        assert_eq!(query, triples(&[2, 4]));
+    }
+
+    #[test]
+    fn delete_insert_leaves_the_store_unchanged() {
+        let dataset = CompressedRdfTriples::from_triples("state-delete-insert", &triples(&[1, 2]));
+        let mut simulation = StoreSimulation::new(&dataset, StateTracking::Filter).unwrap();
+
+        let mut query = triples(&[1, 3]);
+        assert_eq!(simulation.apply(UpdateKind::DeleteInsert, &mut query), 1);
+        assert_eq!(query, triples(&[1]));
+
+        // the deleted triples were restored, so they are deleted again
+        let mut query = triples(&[1, 2]);
+        assert_eq!(simulation.apply(UpdateKind::DeleteInsert, &mut query), 2);
+        assert_eq!(simulation.apply(UpdateKind::Delete, &mut query), 2);
+    }
+}
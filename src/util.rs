@@ -1,4 +1,7 @@
-use std::path::{Path, PathBuf};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
 
 pub fn dataset_iter(
     paths: Vec<PathBuf>,
@@ -43,3 +46,120 @@ pub fn changeset_file_iter<P: AsRef<Path>>(
                 .unwrap_or(true)
         })
 }
+
+/// The triples removed and added by one changeset of a live system, e.g. `000001.removed.nt` and `000001.added.nt`
+pub struct ChangesetPair {
+    /// path of the changeset relative to the changeset directory, without the `.added`/`.removed` suffix
+    pub name: PathBuf,
+    pub removed: Option<PathBuf>,
+    pub added: Option<PathBuf>,
+}
+
+/// Key that orders names by the numbers embedded in them, compared numerically, e.g. `2015/04/28/13/000001`
+/// or `20230101T120000Z`. Leading zeros are stripped so that comparing (length, digits) compares the values.
+fn timestamp_key(name: &Path) -> Vec<(usize, String)> {
+    name.to_string_lossy()
+        .split(|c: char| !c.is_ascii_digit())
+        .filter(|digits| !digits.is_empty())
+        .map(|digits| {
+            let digits = digits.trim_start_matches('0');
+            (digits.len(), digits.to_owned())
+        })
+        .collect()
+}
+
+/// Finds the `*.added.<extension>` and `*.removed.<extension>` files below `path` and pairs them by name.
+/// The pairs are ordered chronologically by the timestamp embedded in their names and directories
+/// (see [`timestamp_key`]), i.e. in the order in which the live system applied them.
+pub fn changeset_pairs<P: AsRef<Path>>(path: P, extension: &str) -> walkdir::Result<Vec<ChangesetPair>> {
+    let root = path.as_ref();
+    let mut pairs: BTreeMap<PathBuf, ChangesetPair> = BTreeMap::new();
+
+    for entry in changeset_file_iter(root, extension) {
+        let path = entry?.into_path();
+        let relative = path.strip_prefix(root).unwrap_or(&path);
+
+        let stem = relative.with_extension("");
+        let (name, added) = match stem.extension().and_then(|ext| ext.to_str()) {
+            Some("added") => (stem.with_extension(""), true),
+            Some("removed") => (stem.with_extension(""), false),
+            _ => {
+                eprintln!("Warning: ignoring {path:?}, it is neither an .added nor a .removed file");
+                continue;
+            },
+        };
+
+        let pair =
+            pairs.entry(name.clone()).or_insert_with(|| ChangesetPair { name, removed: None, added: None });
+
+        if added {
+            pair.added = Some(path);
+        } else {
+            pair.removed = Some(path);
+        }
+    }
+
+    let mut pairs: Vec<_> = pairs.into_values().collect();
+    pairs.sort_by_cached_key(|pair| timestamp_key(&pair.name));
+
+    Ok(pairs)
+}
+
+/// Appends `suffix` to the file name of `path`, e.g. `queries.txt` and `.manifest.jsonl` give `queries.txt.manifest.jsonl`
+pub fn with_file_name_suffix<P: AsRef<Path>>(path: P, suffix: &str) -> PathBuf {
+    let mut path = path.as_ref().as_os_str().to_owned();
+    path.push(suffix);
+    PathBuf::from(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timestamp_keys_compare_numerically() {
+        let key = |name: &str| timestamp_key(Path::new(name));
+
+        assert!(key("2015/04/28/9/000010") < key("2015/04/28/10/000002"));
+        assert!(key("20230101T120000Z") < key("20230101T130000Z"));
+        assert!(key("2015/04/28/13/000001") < key("2015/04/28/13/000001-extra-1"));
+        assert_eq!(key("changes/007"), key("changes-7"));
+    }
+
+    #[test]
+    fn changeset_pairs_are_ordered_chronologically() {
+        let root =
+            std::env::temp_dir().join(format!("{}-{}-changeset-pairs", env!("CARGO_PKG_NAME"), std::process::id()));
+
+        for file in [
+            "2015/04/28/10/000002.added.nt",
+            "2015/04/28/10/000002.removed.nt",
+            "2015/04/28/9/000010.added.nt",
+            "2015/04/28/9/000002.removed.nt",
+            "2015/04/28/9/000003.unknown.nt",
+            "2015/04/28/9/000004.added.txt",
+        ] {
+            let path = root.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "").unwrap();
+        }
+
+        let pairs = changeset_pairs(&root, "nt");
+        std::fs::remove_dir_all(&root).unwrap();
+
+        let pairs: Vec<_> = pairs
+            .unwrap()
+            .into_iter()
+            .map(|ChangesetPair { name, removed, added }| (name, removed.is_some(), added.is_some()))
+            .collect();
+
+        assert_eq!(
+            pairs,
+            [
+                (PathBuf::from("2015/04/28/9/000002"), true, false),
+                (PathBuf::from("2015/04/28/9/000010"), false, true),
+                (PathBuf::from("2015/04/28/10/000002"), true, true),
+            ]
+        );
+    }
+}
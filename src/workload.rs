@@ -0,0 +1,252 @@
+//! Workload files describing several generation phases that share one loaded compressor state and main dataset.
+//!
+//! A workload is a TOML or YAML file, e.g.
+//!
+//! ```toml
+//! compressor_state = "dataset.compressor_state"
+//! main_dataset = "dataset.compressed_nt"
+//! seed = 42
+//!
+//! [[phases]]
+//! name = "warmup"
+//! generator = { type = "randomized", allow_duplicates = true }
+//! query_specs = ["100x10", "10x10-1000"]
+//! output = { path = "warmup.rq" }
+//!
+//! [[phases]]
+//! generator = { type = "changeset", compressed_changesets = "changesets", changeset_type = "fixed-size" }
+//! query_specs = ["50x1%c"]
+//! output_order = "randomized"
+//! update_kind = "delete-insert"
+//! output = { path = "changesets", layout = "shards", shards = 4 }
+//!
+//! [[phases]]
+//! replicate = { datasets = ["replay"], recursive = true }
+//! output = { path = "replay.rq", prefixes = ["dbr=http://dbpedia.org/resource/"], pretty = true }
+//! ```
+//!
+//! Every phase either has a `generator`, which takes the same options as the subcommands of `generate`,
+//! or `replicate`, whose limits are given inside of it. Options that only apply to generation (e.g. `overlap`
+//! or `seed`) are rejected for replicate phases. Relative paths are relative to the working directory.
+
+use crate::{
+    query_spec::QuerySpecOpt,
+    rdf::{prefix::PrefixDecl, triple_compressor::decompressor::RdfTripleDecompressor},
+    sparql::{
+        output::{OutputLayout, QueryOutput},
+        overlap::OverlapTarget,
+        serialize::SerializationOpts,
+        state::StateTracking,
+        OutputOrder, UpdateKind,
+    },
+    GenerateType,
+};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Workload {
+    pub compressor_state: PathBuf,
+
+    /// required if at least one phase has a generator
+    pub main_dataset: Option<PathBuf>,
+
+    /// seed from which the seeds of all phases without their own seed are derived
+    pub seed: Option<u64>,
+
+    pub phases: Vec<Phase>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Phase {
+    pub name: Option<String>,
+
+    pub generator: Option<GenerateType>,
+
+    pub replicate: Option<ReplicateSource>,
+
+    #[serde(default)]
+    pub query_specs: Vec<QuerySpecOpt>,
+
+    pub query_spec_file: Option<PathBuf>,
+
+    pub output_order: Option<OutputOrder>,
+
+    #[serde(default)]
+    pub update_kind: UpdateKind,
+
+    pub state_tracking: Option<StateTracking>,
+
+    pub overlap: Option<f64>,
+
+    pub overlap_with: Option<OverlapTarget>,
+
+    pub misses: Option<f64>,
+
+    pub miss_dataset: Option<PathBuf>,
+
+    #[serde(default)]
+    pub exclude: Vec<PathBuf>,
+
+    pub max_bytes: Option<usize>,
+
+    pub output: PhaseOutput,
+
+    pub seed: Option<u64>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ReplicateSource {
+    pub datasets: Vec<PathBuf>,
+
+    #[serde(default)]
+    pub recursive: bool,
+
+    pub max_triples: Option<usize>,
+
+    pub max_bytes: Option<usize>,
+
+    #[serde(default)]
+    pub merge: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PhaseOutput {
+    pub path: PathBuf,
+
+    #[serde(default)]
+    pub append: bool,
+
+    #[serde(default)]
+    pub layout: OutputLayout,
+
+    #[serde(default = "default_shards")]
+    pub shards: usize,
+
+    #[serde(default)]
+    pub prefixes: Vec<PrefixDecl>,
+
+    #[serde(default)]
+    pub learn_prefixes: usize,
+
+    #[serde(default)]
+    pub pretty: bool,
+}
+
+fn default_shards() -> usize {
+    1
+}
+
+impl PhaseOutput {
+    /// The query output of the phase, prefixes are learned from the given compressor state
+    pub fn into_query_output(self, decompressor: &RdfTripleDecompressor) -> QueryOutput {
+        let PhaseOutput { path, append, layout, shards, prefixes, learn_prefixes, pretty } = self;
+        let serialization = SerializationOpts { prefixes, learn_prefixes, pretty }.into_serialization(decompressor);
+
+        QueryOutput { path, append, layout, n_shards: shards, serialization }
+    }
+}
+
+impl Phase {
+    /// Name of the phase for progress output, `ix` is its position in the workload
+    pub fn display_name(&self, ix: usize) -> String {
+        self.name.clone().unwrap_or_else(|| format!("phase {ix}"))
+    }
+}
+
+impl Workload {
+    /// Reads and validates a workload file, the format is chosen by the extension (`.toml`, `.yaml` or `.yml`)
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn std::error::Error>> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)?;
+
+        let workload: Workload = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => toml::from_str(&contents).map_err(|e| format!("{path:?}: {e}"))?,
+            Some("yaml" | "yml") => serde_yaml::from_str(&contents).map_err(|e| format!("{path:?}: {e}"))?,
+            _ => return Err(format!("{path:?}: unknown workload format, expected a .toml, .yaml or .yml file").into()),
+        };
+
+        for (ix, phase) in workload.phases.iter().enumerate() {
+            let name = phase.display_name(ix);
+
+            match (&phase.generator, &phase.replicate) {
+                (Some(_), None) => {
+                    if workload.main_dataset.is_none() {
+                        return Err(
+                            format!("{path:?}: {name} has a generator but the workload has no main_dataset").into()
+                        );
+                    }
+                },
+                (None, Some(_)) => {
+                    let generator_only: Vec<_> = [
+                        ("query_specs", !phase.query_specs.is_empty()),
+                        ("query_spec_file", phase.query_spec_file.is_some()),
+                        ("output_order", phase.output_order.is_some()),
+                        ("state_tracking", phase.state_tracking.is_some()),
+                        ("overlap", phase.overlap.is_some()),
+                        ("overlap_with", phase.overlap_with.is_some()),
+                        ("misses", phase.misses.is_some()),
+                        ("miss_dataset", phase.miss_dataset.is_some()),
+                        ("exclude", !phase.exclude.is_empty()),
+                        ("max_bytes", phase.max_bytes.is_some()),
+                        ("seed", phase.seed.is_some()),
+                    ]
+                    .into_iter()
+                    .filter_map(|(field, is_set)| is_set.then_some(field))
+                    .collect();
+
+                    if !generator_only.is_empty() {
+                        return Err(format!(
+                            "{path:?}: {name} replicates datasets and cannot have {}",
+                            generator_only.join(", ")
+                        )
+                        .into());
+                    }
+                },
+                _ => return Err(format!("{path:?}: {name} must have exactly one of generator and replicate").into()),
+            }
+
+            if let Some(ReplicateSource { max_triples: Some(0), .. } | ReplicateSource { max_bytes: Some(0), .. }) =
+                phase.replicate
+            {
+                return Err(format!("{path:?}: {name} must allow at least one triple and byte per query").into());
+            }
+
+            if let Some(ReplicateSource { merge: true, max_triples: None, max_bytes: None, .. }) = phase.replicate {
+                return Err(
+                    format!("{path:?}: {name} merges datasets but has neither max_triples nor max_bytes").into()
+                );
+            }
+
+            if phase.max_bytes == Some(0) {
+                return Err(format!("{path:?}: {name} must allow at least one byte per query").into());
+            }
+
+            let limits_bytes = phase.max_bytes.is_some()
+                || matches!(phase.replicate, Some(ReplicateSource { max_bytes: Some(_), .. }));
+            let PhaseOutput { prefixes, learn_prefixes, pretty, .. } = &phase.output;
+
+            if limits_bytes && (!prefixes.is_empty() || *learn_prefixes > 0 || *pretty) {
+                return Err(
+                    format!("{path:?}: {name} limits the bytes per query and cannot use prefixes or pretty").into()
+                );
+            }
+
+            if phase.max_bytes.is_some() && (phase.misses.is_some() || phase.overlap.is_some()) {
+                return Err(
+                    format!("{path:?}: {name} limits the bytes per query and cannot use misses or overlap").into()
+                );
+            }
+
+            if phase.output.shards == 0 {
+                return Err(format!("{path:?}: {name} must have at least one shard").into());
+            }
+        }
+
+        Ok(workload)
+    }
+}